@@ -1,372 +1,2583 @@
-use std::path::Path;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+    sync::Arc,
+};
 
 use chrono::Utc;
-use image::GenericImageView;
-use tauri::{AppHandle, State};
+use image::RgbaImage;
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::Semaphore;
+use tracing::Instrument;
 use uuid::Uuid;
 
 use crate::{
-    error::{AppError, AppResult},
+    error::{AppError, AppResult, CommandError},
+    logging,
     models::{
-        Child, ChildInputs, ChildMode, ChildOutputs, ChildResult, ChildType, EditRequest,
-        GenerateRequest, OpenRouterSnapshot, Project, ProjectSummary, Resolution,
+        AppSettings, AspectMismatch, AtlasExport, Child, ChildDefaults, ChildInputs, ChildMode,
+        ChildNode, ChildOutputs, ChildResult, ChildSummary, ChildType, ColorMapping,
+        CompletionMetadata, ConfigStatus, DiskUsage, DownscaleMode, EditRequest, ExportFailure,
+        FrameOrder, GenerateRequest, GenerationPreview, GenerationProgress, GenerationStage,
+        ImageDiff, ImageTransform, LogEntry, OpenRouterSnapshot, OutputFormat, PaletteSwatch,
+        Project, ProjectImageExport, ProjectList, ProjectSummary, PruneReport, Rect,
+        ReferenceCompression, Resolution, ScaleFilter, SelfCheckReport, SettingsSummary,
+        SlicedSheet,
     },
     openrouter::GenerateImageRequest,
-    prompt, storage, AppState,
+    prompt::{self, PromptExample, StylePreset},
+    settings,
+    storage::{self, ChromakeySettings},
+    AppState,
 };
 
 #[tauri::command]
-pub fn list_projects(app: AppHandle) -> Result<Vec<ProjectSummary>, String> {
-    wrap_cmd(|| {
-        let projects = storage::list_project_records(&app)?
-            .into_iter()
-            .map(|record| record.to_summary())
-            .collect();
-        Ok(projects)
+pub fn list_projects(
+    app: AppHandle,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<ProjectList, CommandError> {
+    wrap_cmd("list_projects", || {
+        let scan = storage::list_project_records(&app, offset.unwrap_or(0), limit)?;
+        Ok(ProjectList {
+            projects: scan.projects,
+            errored: scan.errored,
+            total: scan.total,
+        })
+    })
+}
+
+const DEFAULT_LOG_LIMIT: usize = 200;
+
+/// The newest in-app log entries, oldest first, for a diagnostics view.
+#[tauri::command]
+pub fn get_recent_logs(limit: Option<usize>) -> Vec<LogEntry> {
+    logging::recent_entries(limit.unwrap_or(DEFAULT_LOG_LIMIT))
+}
+
+#[tauri::command]
+pub fn self_check(app: AppHandle) -> Result<SelfCheckReport, CommandError> {
+    wrap_cmd("self_check", || storage::self_check(&app))
+}
+
+#[tauri::command]
+pub fn project_disk_usage(app: AppHandle, project_id: String) -> Result<DiskUsage, CommandError> {
+    wrap_cmd("project_disk_usage", || {
+        storage::project_disk_usage(&app, &project_id)
+    })
+}
+
+#[tauri::command]
+pub fn all_projects_disk_usage(app: AppHandle) -> Result<DiskUsage, CommandError> {
+    wrap_cmd("all_projects_disk_usage", || {
+        storage::all_projects_disk_usage(&app)
+    })
+}
+
+/// With `dry_run`, lists what would be deleted without touching anything.
+#[tauri::command]
+pub fn prune_project(
+    app: AppHandle,
+    project_id: String,
+    dry_run: bool,
+) -> Result<PruneReport, CommandError> {
+    wrap_cmd("prune_project", || {
+        storage::prune_project(&app, &project_id, dry_run)
+    })
+}
+
+#[tauri::command]
+pub fn verify_project(app: AppHandle, project_id: String) -> Result<Vec<String>, CommandError> {
+    wrap_cmd("verify_project", || {
+        storage::verify_project(&app, &project_id)
+    })
+}
+
+#[tauri::command]
+pub fn get_project(app: AppHandle, project_id: String) -> Result<Project, CommandError> {
+    wrap_cmd("get_project", || storage::load_project(&app, &project_id))
+}
+
+#[tauri::command]
+pub fn list_children_summary(
+    app: AppHandle,
+    project_id: String,
+) -> Result<Vec<ChildSummary>, CommandError> {
+    wrap_cmd("list_children_summary", || {
+        storage::list_child_summaries(&app, &project_id)
+    })
+}
+
+#[tauri::command]
+pub fn reorder_children(
+    app: AppHandle,
+    project_id: String,
+    ordered_ids: Vec<String>,
+) -> Result<Vec<String>, CommandError> {
+    wrap_cmd("reorder_children", || {
+        storage::reorder_children(&app, &project_id, ordered_ids)
     })
 }
 
 #[tauri::command]
-pub fn get_project(app: AppHandle, project_id: String) -> Result<Project, String> {
-    wrap_cmd(|| storage::load_project(&app, &project_id))
+pub fn get_child_lineage(
+    app: AppHandle,
+    project_id: String,
+) -> Result<Vec<ChildNode>, CommandError> {
+    wrap_cmd("get_child_lineage", || {
+        let project = storage::load_project(&app, &project_id)?;
+        Ok(lineage_forest(&project.children))
+    })
 }
 
 #[tauri::command]
 pub fn create_project(
     app: AppHandle,
     optional_name: Option<String>,
-) -> Result<ProjectSummary, String> {
-    wrap_cmd(|| {
+) -> Result<ProjectSummary, CommandError> {
+    wrap_cmd("create_project", || {
         let record = storage::create_project_record(&app, optional_name)?;
         Ok(record.to_summary())
     })
 }
 
 #[tauri::command]
-pub fn delete_project(app: AppHandle, project_id: String) -> Result<(), String> {
-    wrap_cmd(|| storage::delete_project(&app, &project_id))
+pub fn rename_project(
+    app: AppHandle,
+    project_id: String,
+    name: String,
+) -> Result<ProjectSummary, CommandError> {
+    wrap_cmd("rename_project", || {
+        let name = non_empty(&name).ok_or_else(|| AppError::invalid("project name is required"))?;
+        let record = storage::update_project_name(&app, &project_id, Some(name.to_string()))?;
+        Ok(record.to_summary())
+    })
+}
+
+#[tauri::command]
+pub fn duplicate_project(
+    app: AppHandle,
+    project_id: String,
+    new_name: Option<String>,
+) -> Result<ProjectSummary, CommandError> {
+    wrap_cmd("duplicate_project", || {
+        let record = storage::duplicate_project(&app, &project_id, new_name)?;
+        Ok(record.to_summary())
+    })
+}
+
+#[tauri::command]
+pub async fn export_project_archive(
+    app: AppHandle,
+    project_id: String,
+    destination_path: String,
+) -> Result<String, CommandError> {
+    wrap_cmd_async("export_project_archive", async move {
+        tauri::async_runtime::spawn_blocking(move || {
+            storage::export_project_archive(&app, &project_id, Path::new(&destination_path))?;
+            Ok(destination_path)
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join archive export task: {error}")))?
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn import_project_archive(
+    app: AppHandle,
+    archive_path: String,
+) -> Result<ProjectSummary, CommandError> {
+    wrap_cmd_async("import_project_archive", async move {
+        tauri::async_runtime::spawn_blocking(move || {
+            let record = storage::import_project_archive(&app, Path::new(&archive_path))?;
+            Ok(record.to_summary())
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join archive import task: {error}")))?
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn get_project_defaults(
+    app: AppHandle,
+    project_id: String,
+) -> Result<ChildDefaults, CommandError> {
+    wrap_cmd("get_project_defaults", || {
+        Ok(storage::load_project_record(&app, &project_id)?
+            .defaults
+            .unwrap_or_default())
+    })
+}
+
+#[tauri::command]
+pub fn set_project_defaults(
+    app: AppHandle,
+    project_id: String,
+    defaults: ChildDefaults,
+) -> Result<ChildDefaults, CommandError> {
+    wrap_cmd("set_project_defaults", || {
+        let record = storage::set_project_defaults(&app, &project_id, defaults)?;
+        Ok(record.defaults.unwrap_or_default())
+    })
+}
+
+#[tauri::command]
+pub fn get_project_variables(
+    app: AppHandle,
+    project_id: String,
+) -> Result<BTreeMap<String, String>, CommandError> {
+    wrap_cmd("get_project_variables", || {
+        Ok(storage::load_project_record(&app, &project_id)?.variables)
+    })
+}
+
+#[tauri::command]
+pub fn set_project_variables(
+    app: AppHandle,
+    project_id: String,
+    variables: BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>, CommandError> {
+    wrap_cmd("set_project_variables", || {
+        prompt::validate_variables(&variables)?;
+        let record = storage::set_project_variables(&app, &project_id, variables)?;
+        Ok(record.variables)
+    })
+}
+
+#[tauri::command]
+pub fn delete_project(app: AppHandle, project_id: String) -> Result<(), CommandError> {
+    wrap_cmd("delete_project", || {
+        storage::delete_project(&app, &project_id)
+    })
+}
+
+/// Returns the ids of children that were edited from the deleted one and now point at a
+/// missing base.
+#[tauri::command]
+pub fn delete_child(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+) -> Result<Vec<String>, CommandError> {
+    wrap_cmd("delete_child", || {
+        storage::delete_child(&app, &project_id, &child_id)
+    })
+}
+
+#[tauri::command]
+pub fn update_child_annotations(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+    notes: Option<String>,
+    label_color: Option<String>,
+) -> Result<Child, CommandError> {
+    wrap_cmd("update_child_annotations", || {
+        let label_color = label_color
+            .as_deref()
+            .and_then(non_empty)
+            .map(normalize_hex_color)
+            .transpose()?;
+        storage::update_child_annotations(&app, &project_id, &child_id, notes, label_color)
+    })
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn export_image_to_path(
+    source_image_path: String,
+    destination_path: String,
+    remove_chromakey_background: bool,
+    chromakey_color: Option<[u8; 3]>,
+    scale_factor: Option<f64>,
+    scale_filter: Option<ScaleFilter>,
+    format: Option<OutputFormat>,
+    background_color: Option<[u8; 4]>,
+) -> Result<String, CommandError> {
+    wrap_cmd_async("export_image_to_path", async move {
+        let scale = scale_factor
+            .map(integer_scale_factor)
+            .transpose()?
+            .map(|factor| (factor, scale_filter.unwrap_or_default()));
+        let source_path = std::path::PathBuf::from(source_image_path);
+        let destination_path = std::path::PathBuf::from(destination_path);
+
+        tauri::async_runtime::spawn_blocking(move || {
+            storage::export_image_to_path(
+                &source_path,
+                &destination_path,
+                remove_chromakey_background,
+                chromakey_color,
+                scale,
+                format.unwrap_or_default(),
+                background_color,
+            )
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join export task: {error}")))?
+    })
+    .await
+}
+
+/// Exports every child's primary image (or all of its images) into `destination_dir`, named
+/// after the child. A failing image is recorded in the summary and doesn't stop the rest.
+#[tauri::command]
+pub async fn export_project_images(
+    app: AppHandle,
+    project_id: String,
+    destination_dir: String,
+    remove_chromakey_background: bool,
+    all_images: Option<bool>,
+    format: Option<OutputFormat>,
+) -> Result<ProjectImageExport, CommandError> {
+    wrap_cmd_async("export_project_images", async move {
+        let project = storage::load_project(&app, &project_id)?;
+        let destination_dir = std::path::PathBuf::from(destination_dir);
+        let all_images = all_images.unwrap_or(false);
+        let format = format.unwrap_or_default();
+
+        tauri::async_runtime::spawn_blocking(move || {
+            std::fs::create_dir_all(&destination_dir)?;
+            let mut summary = ProjectImageExport::default();
+            let mut used_names = HashSet::new();
+            for child in &project.children {
+                let sources = if all_images {
+                    child.outputs.image_paths.iter().collect::<Vec<_>>()
+                } else {
+                    child.outputs.primary_image_path.iter().collect()
+                };
+                let stem = child.name.replace(['/', '\\'], "_");
+                for (index, source) in sources.iter().enumerate() {
+                    let base = if sources.len() > 1 {
+                        format!("{stem}_{index}")
+                    } else {
+                        stem.clone()
+                    };
+                    let file_name =
+                        unique_export_name(&mut used_names, &base, &child.id, format.extension());
+                    match storage::export_image_to_path(
+                        Path::new(source),
+                        &destination_dir.join(file_name),
+                        remove_chromakey_background,
+                        child.inputs.chromakey_color,
+                        None,
+                        format,
+                        None,
+                    ) {
+                        Ok(path) => summary.exported.push(path),
+                        Err(error) => summary.failed.push(ExportFailure {
+                            child_id: child.id.clone(),
+                            source_path: source.to_string(),
+                            error: error.to_string(),
+                        }),
+                    }
+                }
+            }
+            Ok(summary)
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join export task: {error}")))?
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn export_frames_to_watch_folder(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+    image_index: Option<usize>,
+    watch_dir: String,
+    file_prefix: Option<String>,
+    clear_stale: bool,
+) -> Result<Vec<String>, CommandError> {
+    wrap_cmd_async("export_frames_to_watch_folder", async move {
+        let child = storage::load_child(&app, &project_id, &child_id)?;
+        let source_path = std::path::PathBuf::from(child_image_path(&child, image_index)?);
+        let grid = frame_grid_or_single(&child);
+        let file_prefix = file_prefix
+            .as_deref()
+            .and_then(non_empty)
+            .unwrap_or(&child.name)
+            .to_string();
+        let watch_dir = std::path::PathBuf::from(watch_dir);
+
+        tauri::async_runtime::spawn_blocking(move || {
+            storage::export_frames_to_watch_folder(
+                &source_path,
+                grid,
+                &watch_dir,
+                &file_prefix,
+                clear_stale,
+            )
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join export task: {error}")))?
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn slice_sprite_sheet(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+    image_index: Option<usize>,
+    trim: bool,
+    frame_order: Option<FrameOrder>,
+) -> Result<SlicedSheet, CommandError> {
+    wrap_cmd_async("slice_sprite_sheet", async move {
+        let child = storage::load_child(&app, &project_id, &child_id)?;
+        if child.mode != ChildMode::Sprite {
+            return Err(AppError::invalid(
+                "slice_sprite_sheet requires a sprite sheet child",
+            ));
+        }
+        let index = image_index.unwrap_or(0);
+        let source_path = std::path::PathBuf::from(child_image_path(&child, Some(index))?);
+        let grid = frame_grid_with_order(&child, frame_order);
+
+        tauri::async_runtime::spawn_blocking(move || {
+            storage::slice_sprite_sheet(
+                &app,
+                &project_id,
+                &child_id,
+                index,
+                &source_path,
+                grid,
+                trim,
+            )
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join slice task: {error}")))?
+    })
+    .await
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn export_sprite_gif(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+    image_index: Option<usize>,
+    destination_path: String,
+    fps: u32,
+    background_color: Option<[u8; 3]>,
+    frame_order: Option<FrameOrder>,
+) -> Result<String, CommandError> {
+    wrap_cmd_async("export_sprite_gif", async move {
+        if !(1..=100).contains(&fps) {
+            return Err(AppError::invalid("fps must be between 1 and 100"));
+        }
+
+        let child = storage::load_child(&app, &project_id, &child_id)?;
+        if child.mode != ChildMode::Sprite {
+            return Err(AppError::invalid(
+                "export_sprite_gif requires a sprite sheet child",
+            ));
+        }
+        let source_path = std::path::PathBuf::from(child_image_path(&child, image_index)?);
+        let grid = frame_grid_with_order(&child, frame_order);
+        let destination_path = std::path::PathBuf::from(destination_path);
+
+        tauri::async_runtime::spawn_blocking(move || {
+            storage::export_sprite_gif(&source_path, grid, &destination_path, fps, background_color)
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join gif export task: {error}")))?
+    })
+    .await
+}
+
+const MAX_EXPORT_FRAME_EDGE: u32 = 4096;
+
+/// Exports the sheet with every frame resized to exactly `frame_width` x `frame_height`,
+/// e.g. 32x32 for a game; `Nearest` keeps pixel art crisp.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn export_sheet_at_frame_size(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+    image_index: Option<usize>,
+    frame_width: u32,
+    frame_height: u32,
+    filter: ScaleFilter,
+    destination_path: String,
+) -> Result<String, CommandError> {
+    wrap_cmd_async("export_sheet_at_frame_size", async move {
+        let valid_edge = 1..=MAX_EXPORT_FRAME_EDGE;
+        if !valid_edge.contains(&frame_width) || !valid_edge.contains(&frame_height) {
+            return Err(AppError::invalid(format!(
+                "frame size must be between 1 and {MAX_EXPORT_FRAME_EDGE} pixels per side"
+            )));
+        }
+
+        let child = storage::load_child(&app, &project_id, &child_id)?;
+        if child.mode != ChildMode::Sprite {
+            return Err(AppError::invalid(
+                "export_sheet_at_frame_size requires a sprite sheet child",
+            ));
+        }
+        let source_path = std::path::PathBuf::from(child_image_path(&child, image_index)?);
+        let grid = frame_grid_or_single(&child);
+        let destination_path = std::path::PathBuf::from(destination_path);
+
+        tauri::async_runtime::spawn_blocking(move || {
+            storage::export_sheet_at_frame_size(
+                &source_path,
+                grid,
+                frame_width,
+                frame_height,
+                filter,
+                &destination_path,
+            )
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join sheet export task: {error}")))?
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn export_atlas(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+    image_index: Option<usize>,
+    destination_dir: String,
+    name: Option<String>,
+) -> Result<AtlasExport, CommandError> {
+    wrap_cmd_async("export_atlas", async move {
+        let child = storage::load_child(&app, &project_id, &child_id)?;
+        let source_path = std::path::PathBuf::from(child_image_path(&child, image_index)?);
+        let grid = frame_grid_or_single(&child);
+        let name = name
+            .as_deref()
+            .and_then(non_empty)
+            .unwrap_or(&child.name)
+            .to_string();
+        let destination_dir = std::path::PathBuf::from(destination_dir);
+
+        tauri::async_runtime::spawn_blocking(move || {
+            storage::export_atlas(&source_path, grid, &destination_dir, &name)
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join atlas export task: {error}")))?
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn set_primary_image(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+    image_index: usize,
+) -> Result<Child, CommandError> {
+    wrap_cmd("set_primary_image", || {
+        let mut child = storage::load_child(&app, &project_id, &child_id)?;
+        child.outputs.primary_image_path = Some(child_image_path(&child, Some(image_index))?);
+        storage::save_child(&app, &child)?;
+        storage::touch_project_for_child(&app, &child)?;
+        Ok(child)
+    })
+}
+
+#[tauri::command]
+pub async fn generate_image(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    req: GenerateRequest,
+    request_id: Option<String>,
+) -> Result<ChildResult, CommandError> {
+    wrap_cmd_async(
+        "generate_image",
+        run_generate(&app, &state, req, request_id.as_deref()),
+    )
+    .await
+}
+
+/// `online` defaults to true; pass false to skip the network calls.
+#[tauri::command]
+pub async fn check_openrouter_status(
+    state: State<'_, AppState>,
+    online: Option<bool>,
+) -> Result<ConfigStatus, CommandError> {
+    Ok(state.openrouter.check_status(online.unwrap_or(true)).await)
+}
+
+/// Re-reads `.env` and the saved settings and rebuilds the OpenRouter config, so a key added
+/// after launch works without a restart. Attribution set at runtime is replaced by the
+/// reloaded values.
+#[tauri::command]
+pub async fn reload_openrouter_config(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ConfigStatus, CommandError> {
+    crate::load_env_files(true);
+    state
+        .openrouter
+        .replace_config(settings::openrouter_config(&app));
+    Ok(state.openrouter.check_status(false).await)
+}
+
+#[tauri::command]
+pub fn get_settings(app: AppHandle) -> Result<SettingsSummary, CommandError> {
+    wrap_cmd("get_settings", || {
+        Ok(settings::summarize(&settings::load_settings(&app)?))
+    })
+}
+
+/// Saves the settings and applies them to OpenRouter right away. Fields left out keep their
+/// saved value; blank ones are cleared.
+#[tauri::command]
+pub fn update_settings(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    settings: AppSettings,
+) -> Result<SettingsSummary, CommandError> {
+    wrap_cmd("update_settings", || {
+        let saved = settings::update_settings(&app, settings)?;
+        state
+            .openrouter
+            .replace_config(settings::openrouter_config(&app));
+        Ok(settings::summarize(&saved))
+    })
+}
+
+/// Changes how OpenRouter attributes requests from this app. Generations already running
+/// keep the attribution they started with.
+#[tauri::command]
+pub fn set_openrouter_attribution(
+    state: State<'_, AppState>,
+    referer: Option<String>,
+    title: Option<String>,
+) -> Result<(), CommandError> {
+    wrap_cmd("set_openrouter_attribution", || {
+        state.openrouter.set_attribution(referer, title);
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn cancel_generation(state: State<'_, AppState>, request_id: String) -> bool {
+    state.cancel_generation(&request_id)
+}
+
+#[tauri::command]
+pub fn list_examples() -> Vec<PromptExample> {
+    prompt::PROMPT_EXAMPLES.to_vec()
+}
+
+#[tauri::command]
+pub fn list_camera_angles() -> Vec<&'static str> {
+    prompt::camera_angles()
+}
+
+#[tauri::command]
+pub fn list_style_presets() -> Vec<StylePreset> {
+    prompt::STYLE_PRESETS.to_vec()
+}
+
+#[tauri::command]
+pub async fn generate_from_example(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    example_id: String,
+    project_id: Option<String>,
+    resolution: Option<Resolution>,
+) -> Result<ChildResult, CommandError> {
+    wrap_cmd_async("generate_from_example", async {
+        let req = prompt::find_example(&example_id)?.to_generate_request(project_id, resolution);
+        run_generate(&app, &state, req, None).await
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn preview_generation(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    mut req: GenerateRequest,
+) -> Result<GenerationPreview, CommandError> {
+    wrap_cmd("preview_generation", || {
+        if let Some(project_id) = req.project_id.as_deref() {
+            let record = storage::load_project_record(&app, project_id)?;
+            if let Some(defaults) = record.defaults.as_ref() {
+                apply_project_defaults(&mut req, defaults);
+            }
+            prompt::apply_variables(&mut req, &record.variables)?;
+        } else {
+            prompt::apply_variables(&mut req, &BTreeMap::new())?;
+        }
+        validate_generate_request(&req)?;
+        let (_, prompt_text, aspect_ratio) = build_generate_prompt(&req)?;
+        let (resolution, _) = generate_resolution(&req);
+        let (model, sanitized_payload) =
+            state.openrouter.preview_payload(&GenerateImageRequest {
+                prompt: prompt_text.clone(),
+                image_data_urls: req.reference_data_urls().cloned().collect(),
+                mask_data_url: None,
+                aspect_ratio: aspect_ratio.clone(),
+                resolution,
+                n: req.n,
+                seed: req.seed,
+                model: req.model.clone(),
+                system_prompt: req.system_prompt.clone(),
+                debug: false,
+            })?;
+        Ok(GenerationPreview {
+            prompt: prompt_text,
+            aspect_ratio,
+            model,
+            sanitized_payload,
+            key_color_warning: prompt::key_color_conflict(&req),
+        })
+    })
+}
+
+/// Fills fields the request left empty from the project's defaults.
+fn apply_project_defaults(req: &mut GenerateRequest, defaults: &ChildDefaults) {
+    if non_empty_opt(req.style.as_deref()).is_none() && req.style_preset.is_none() {
+        req.style = defaults.style.clone();
+    }
+    if non_empty_opt(req.camera_angle.as_deref()).is_none() {
+        req.camera_angle = defaults.camera_angle.clone();
+    }
+    req.resolution = req.resolution.or(defaults.resolution);
+    req.rows = req.rows.or(defaults.rows);
+    req.cols = req.cols.or(defaults.cols);
+}
+
+fn build_generate_prompt(req: &GenerateRequest) -> AppResult<(ChildMode, String, Option<String>)> {
+    if req.sprite_mode {
+        let rows = req
+            .rows
+            .ok_or_else(|| AppError::invalid("rows is required in sprite mode"))?;
+        let cols = req
+            .cols
+            .ok_or_else(|| AppError::invalid("cols is required in sprite mode"))?;
+        Ok((
+            ChildMode::Sprite,
+            prompt::build_sprite_prompt(req)?,
+            Some(prompt::choose_aspect_ratio(cols, rows).to_string()),
+        ))
+    } else {
+        Ok((ChildMode::Normal, prompt::build_normal_prompt(req)?, None))
+    }
+}
+
+/// The resolution to request and the long edge to aim the output at.
+fn generate_resolution(req: &GenerateRequest) -> (Resolution, u32) {
+    if let Some(long_edge) = req.custom_long_edge {
+        return (Resolution::covering(long_edge), long_edge);
+    }
+
+    let mut resolution = req.resolution.unwrap_or(Resolution::OneK);
+    if req.sprite_mode && req.scale_for_grid {
+        if let (Some(rows), Some(cols)) = (req.rows, req.cols) {
+            resolution = effective_resolution(resolution, rows, cols);
+        }
+    }
+    (resolution, resolution.long_edge())
+}
+
+/// Pixels each frame should get along the sheet's long edge before we ask for a bigger tier.
+const MIN_FRAME_EDGE: u32 = 256;
+const MAX_BATCH_CONCURRENCY: usize = 8;
+
+/// Raises `base` to the smallest tier giving every frame at least `MIN_FRAME_EDGE` pixels
+/// along the grid's longer dimension, capped at 4K. Never lowers the requested tier, so a
+/// 4x4 sheet stays at 1K while a 1x8 strip goes to 2K.
+fn effective_resolution(base: Resolution, rows: u32, cols: u32) -> Resolution {
+    let needed = rows.max(cols).saturating_mul(MIN_FRAME_EDGE);
+    let scaled = Resolution::covering(needed);
+    if scaled.long_edge() > base.long_edge() {
+        scaled
+    } else {
+        base
+    }
+}
+
+/// Runs `generate_child` in a `generate` span, which logs its duration when it closes. The
+/// project and child ids are recorded on the span once they're known.
+async fn run_generate(
+    app: &AppHandle,
+    state: &AppState,
+    req: GenerateRequest,
+    request_id: Option<&str>,
+) -> AppResult<ChildResult> {
+    let span = tracing::info_span!(
+        "generate",
+        request_id,
+        sprite_mode = req.sprite_mode,
+        project_id = req.project_id.as_deref(),
+        child_id = tracing::field::Empty,
+    );
+    generate_child(app, state, req, request_id)
+        .instrument(span)
+        .await
+}
+
+async fn generate_child(
+    app: &AppHandle,
+    state: &AppState,
+    mut req: GenerateRequest,
+    request_id: Option<&str>,
+) -> AppResult<ChildResult> {
+    let existing_record = match req.project_id.as_deref() {
+        Some(project_id) => Some(storage::load_project_record(app, project_id)?),
+        None => None,
+    };
+    if let Some(defaults) = existing_record
+        .as_ref()
+        .and_then(|record| record.defaults.as_ref())
+    {
+        apply_project_defaults(&mut req, defaults);
+    }
+    let no_variables = BTreeMap::new();
+    let variables = existing_record
+        .as_ref()
+        .map_or(&no_variables, |record| &record.variables);
+    prompt::apply_variables(&mut req, variables)?;
+    validate_generate_request(&req)?;
+    let key_color_warning = prompt::key_color_conflict(&req);
+
+    let compression = req.reference_compression.unwrap_or_default();
+    req.image_prior_data_url = req
+        .image_prior_data_url
+        .as_deref()
+        .map(|data_url| prepare_reference(data_url, compression))
+        .transpose()?;
+    req.image_prior_data_urls = req
+        .image_prior_data_urls
+        .iter()
+        .map(|data_url| prepare_reference(data_url, compression))
+        .collect::<AppResult<_>>()?;
+
+    let mut project_record = match existing_record {
+        Some(record) => record,
+        None => storage::create_project_record(app, Some(default_project_name(&req)))?,
+    };
+
+    if let Some(name) = req.name.as_ref().and_then(|value| non_empty(value)) {
+        project_record =
+            storage::update_project_name(app, &project_record.id, Some(name.to_string()))?;
+    }
+    tracing::Span::current().record("project_id", project_record.id.as_str());
+
+    let (mode, prompt_text, aspect_ratio) = build_generate_prompt(&req)?;
+    let (resolution, target_long_edge) = generate_resolution(&req);
+    emit_progress(app, request_id, GenerationStage::Started);
+    emit_request_sent(app, request_id, req.reference_data_urls().next().is_some());
+    let openrouter_response = state
+        .run_cancellable(
+            request_id,
+            state.openrouter.generate_image(GenerateImageRequest {
+                prompt: prompt_text,
+                image_data_urls: req.reference_data_urls().cloned().collect(),
+                mask_data_url: None,
+                aspect_ratio: aspect_ratio.clone(),
+                resolution,
+                n: req.n,
+                seed: req.seed,
+                model: req.model.clone(),
+                system_prompt: req.system_prompt.clone(),
+                debug: req.debug,
+            }),
+        )
+        .await?;
+    emit_progress(app, request_id, GenerationStage::PostProcessing);
+
+    let received_images = openrouter_response.image_data_urls.len();
+    // With several candidates requested, keep every image and point primary at the best one.
+    let (chosen_data_urls, primary_index) = if req.n.is_some_and(|n| n > 1) {
+        let best =
+            best_image_index_for_resolution(&openrouter_response.image_data_urls, target_long_edge);
+        (
+            openrouter_response.image_data_urls.clone(),
+            best.unwrap_or(0),
+        )
+    } else {
+        (
+            choose_best_images_for_resolution(
+                &openrouter_response.image_data_urls,
+                target_long_edge,
+            ),
+            0,
+        )
+    };
+    let child_id = Uuid::new_v4().to_string();
+    tracing::Span::current().record("child_id", child_id.as_str());
+    let sprite_grid = if req.sprite_mode {
+        Some((req.rows.unwrap_or(1), req.cols.unwrap_or(1)))
+    } else {
+        None
+    };
+    let aspect_mismatch = match (aspect_ratio.as_deref(), chosen_data_urls.get(primary_index)) {
+        (Some(ratio), Some(data_url)) => {
+            detect_aspect_mismatch(ratio, data_url, req.pad_to_aspect)?
+        }
+        _ => None,
+    };
+    let output_options = storage::OutputImageOptions {
+        target_long_edge: req.custom_long_edge,
+        apply_chromakey: req.sprite_mode,
+        chromakey_color: req.chromakey_color,
+        chromakey_settings: req.chromakey_settings,
+        palette_colors: req.palette_colors,
+        sprite_grid,
+        edit_mask: None,
+        preserved_regions: None,
+        pad_to_aspect: aspect_mismatch
+            .as_ref()
+            .filter(|mismatch| mismatch.padded)
+            .map(|mismatch| mismatch.requested),
+        measure_seams: req.tileable,
+        verify_grid: req.verify_grid,
+    };
+    let mut image_paths = Vec::new();
+    let mut raw_image_paths = Vec::new();
+    let mut grid_warning = None;
+    let mut seamless_scores = Vec::new();
+    let mut grid_mismatch = None;
+    for saved in write_output_images(
+        app,
+        &project_record.id,
+        &child_id,
+        &chosen_data_urls,
+        output_options,
+    )
+    .await?
+    {
+        image_paths.push(saved.image_path);
+        raw_image_paths.extend(saved.raw_image_path);
+        grid_warning = grid_warning.or(saved.grid_warning);
+        seamless_scores.extend(saved.seamless_score);
+        grid_mismatch = grid_mismatch.or(saved.grid_mismatch);
+    }
+
+    let append_guard = storage::lock_child_appends();
+    let child_name = storage::next_child_name(app, &project_record.id, ChildType::Generate)
+        .inspect_err(|_| storage::discard_child_files(app, &project_record.id, &child_id))?;
+    let child = Child {
+        id: child_id,
+        project_id: project_record.id.clone(),
+        r#type: ChildType::Generate,
+        name: child_name,
+        created_at: Utc::now(),
+        mode,
+        inputs: ChildInputs {
+            rows: req.rows,
+            cols: req.cols,
+            object_description: req.object_description.clone(),
+            style: req.style.clone(),
+            style_preset: req.style_preset.clone(),
+            custom_long_edge: req.custom_long_edge,
+            aspect_ratio: aspect_ratio.clone(),
+            model: req.model.clone(),
+            system_prompt: req.system_prompt.clone(),
+            camera_angle: req.camera_angle.clone(),
+            prompt_text: req.prompt_text.clone(),
+            edit_prompt: None,
+            base_child_id: None,
+            resolution: Some(resolution),
+            image_prior_data_url: req.image_prior_data_url.clone(),
+            image_prior_data_urls: req.image_prior_data_urls.clone(),
+            tileable: req.tileable,
+            base_image_path: None,
+            mask_data_url: None,
+            frame_order: if req.sprite_mode {
+                Some(req.frame_order)
+            } else {
+                None
+            },
+            operation: None,
+            locked_frames: None,
+            regenerated_frames: None,
+            chromakey_color: if req.sprite_mode {
+                req.chromakey_color
+            } else {
+                None
+            },
+            chromakey_settings: if req.sprite_mode {
+                req.chromakey_settings
+            } else {
+                None
+            },
+            palette_colors: req.palette_colors,
+            seed: req.seed,
+            negative_prompt: non_empty_opt(req.negative_prompt.as_deref()).map(str::to_string),
+            prompt_template: if req.sprite_mode {
+                non_empty_opt(req.prompt_template.as_deref()).map(str::to_string)
+            } else {
+                None
+            },
+        },
+        openrouter: OpenRouterSnapshot {
+            model: openrouter_response.model,
+            payload: openrouter_response.sanitized_payload,
+            fallback_attempts: openrouter_response.fallback_attempts,
+        },
+        outputs: ChildOutputs {
+            text: openrouter_response.text,
+            image_paths: image_paths.clone(),
+            primary_image_path: image_paths.get(primary_index).cloned(),
+            completion: note_image_count(
+                openrouter_response.completion,
+                req.n.unwrap_or(1),
+                received_images,
+            ),
+            aspect_mismatch,
+            raw_image_paths,
+            frame_bounds: BTreeMap::new(),
+            repacked_frames: Vec::new(),
+            seamless_scores,
+        },
+        notes: None,
+        label_color: None,
+    };
+
+    storage::append_child(app, &project_record.id, &child)?;
+    drop(append_guard);
+    project_record = storage::load_project_record(app, &project_record.id)?;
+    emit_progress(app, request_id, GenerationStage::Done);
+
+    Ok(ChildResult {
+        project: project_record.to_summary(),
+        child,
+        grid_warning,
+        key_color_warning,
+        grid_mismatch,
+        debug_response: openrouter_response.debug_response,
+    })
+}
+
+#[tauri::command]
+pub async fn edit_image(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    req: EditRequest,
+    request_id: Option<String>,
+) -> Result<ChildResult, CommandError> {
+    wrap_cmd_async(
+        "edit_image",
+        run_edit(&app, &state, req, request_id.as_deref()),
+    )
+    .await
+}
+
+/// Runs each request in `project_id`, at most `concurrency` at a time so a batch stays
+/// under OpenRouter's rate limits. Results come back in request order, and one failed
+/// generation doesn't stop the others.
+#[tauri::command]
+pub async fn batch_generate(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    project_id: String,
+    requests: Vec<GenerateRequest>,
+    concurrency: usize,
+) -> Result<Vec<Result<ChildResult, CommandError>>, CommandError> {
+    wrap_cmd_async("batch_generate", async {
+        if !(1..=MAX_BATCH_CONCURRENCY).contains(&concurrency) {
+            return Err(AppError::invalid(format!(
+                "concurrency must be between 1 and {MAX_BATCH_CONCURRENCY}"
+            )));
+        }
+        storage::load_project_record(&app, &project_id)?;
+
+        let permits = Arc::new(Semaphore::new(concurrency));
+        let tasks: Vec<_> = requests
+            .into_iter()
+            .map(|mut req| {
+                req.project_id = Some(project_id.clone());
+                tauri::async_runtime::spawn(run_batch_item(
+                    app.clone(),
+                    state.inner().clone(),
+                    permits.clone(),
+                    req,
+                ))
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            let result = task
+                .await
+                .map_err(|error| AppError::msg(format!("failed to join generate task: {error}")))
+                .and_then(|result| result);
+            results.push(result.map_err(CommandError::from));
+        }
+        Ok(results)
+    })
+    .await
+}
+
+async fn run_batch_item(
+    app: AppHandle,
+    state: AppState,
+    permits: Arc<Semaphore>,
+    req: GenerateRequest,
+) -> AppResult<ChildResult> {
+    let _permit = permits
+        .acquire_owned()
+        .await
+        .map_err(|_| AppError::Cancelled)?;
+    run_generate(&app, &state, req, None).await
+}
+
+/// Rolls a generated or edited child again from its stored inputs, appending the result as a
+/// sibling. The stored seed is dropped so the model doesn't repeat itself; edits still fall
+/// back to their base child's seed.
+#[tauri::command]
+pub async fn regenerate_child(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    project_id: String,
+    child_id: String,
+) -> Result<ChildResult, CommandError> {
+    wrap_cmd_async("regenerate_child", async {
+        let child = storage::load_child(&app, &project_id, &child_id)?;
+        let inputs = child.inputs;
+        if let Some(operation) = inputs.operation {
+            return Err(AppError::invalid(format!(
+                "child {child_id} was produced by a local {operation} and can't be regenerated"
+            )));
+        }
+        if inputs.regenerated_frames.is_some() {
+            return Err(AppError::invalid(format!(
+                "child {child_id} came from regenerating frames; use regenerate_frames instead"
+            )));
+        }
+
+        match child.r#type {
+            ChildType::Generate => {
+                let req = GenerateRequest {
+                    project_id: Some(project_id),
+                    name: None,
+                    sprite_mode: child.mode == ChildMode::Sprite,
+                    rows: inputs.rows,
+                    cols: inputs.cols,
+                    object_description: inputs.object_description,
+                    style: inputs.style,
+                    camera_angle: inputs.camera_angle,
+                    prompt_text: inputs.prompt_text,
+                    resolution: inputs.resolution,
+                    image_prior_data_url: inputs.image_prior_data_url,
+                    image_prior_data_urls: inputs.image_prior_data_urls,
+                    tileable: inputs.tileable,
+                    verify_grid: false,
+                    pad_to_aspect: child
+                        .outputs
+                        .aspect_mismatch
+                        .is_some_and(|mismatch| mismatch.padded),
+                    frame_order: inputs.frame_order.unwrap_or_default(),
+                    chromakey_color: inputs.chromakey_color,
+                    chromakey_settings: inputs.chromakey_settings,
+                    palette_colors: inputs.palette_colors,
+                    n: None,
+                    seed: None,
+                    negative_prompt: inputs.negative_prompt,
+                    prompt_template: inputs.prompt_template,
+                    style_preset: inputs.style_preset,
+                    custom_long_edge: inputs.custom_long_edge,
+                    scale_for_grid: false,
+                    model: inputs.model,
+                    system_prompt: inputs.system_prompt,
+                    debug: false,
+                    reference_compression: None,
+                };
+                run_generate(&app, &state, req, None).await
+            }
+            ChildType::Edit => {
+                let req = EditRequest {
+                    project_id,
+                    base_child_id: inputs.base_child_id.ok_or_else(|| {
+                        AppError::invalid(format!("edit child {child_id} has no base child"))
+                    })?,
+                    name: None,
+                    edit_prompt: inputs.edit_prompt.ok_or_else(|| {
+                        AppError::invalid(format!("edit child {child_id} has no edit prompt"))
+                    })?,
+                    resolution: inputs.resolution,
+                    base_image_data_url: None,
+                    base_image_path: inputs.base_image_path,
+                    base_image_index: None,
+                    mask_data_url: inputs.mask_data_url,
+                    chromakey_color: inputs.chromakey_color,
+                    chromakey_settings: inputs.chromakey_settings,
+                    palette_colors: inputs.palette_colors,
+                    seed: None,
+                    model: inputs.model,
+                };
+                run_edit(&app, &state, req, None).await
+            }
+        }
+    })
+    .await
+}
+
+/// Runs `edit_child` in an `edit` span, like `run_generate`.
+async fn run_edit(
+    app: &AppHandle,
+    state: &AppState,
+    req: EditRequest,
+    request_id: Option<&str>,
+) -> AppResult<ChildResult> {
+    let span = tracing::info_span!(
+        "edit",
+        request_id,
+        project_id = req.project_id.as_str(),
+        base_child_id = req.base_child_id.as_str(),
+        child_id = tracing::field::Empty,
+    );
+    edit_child(app, state, req, request_id)
+        .instrument(span)
+        .await
+}
+
+async fn edit_child(
+    app: &AppHandle,
+    state: &AppState,
+    req: EditRequest,
+    request_id: Option<&str>,
+) -> AppResult<ChildResult> {
+    let edit_prompt = prompt::build_edit_prompt(&req.edit_prompt)?;
+
+    let mut project_record = storage::load_project_record(app, &req.project_id)?;
+    if let Some(name) = req.name.as_ref().and_then(|value| non_empty(value)) {
+        project_record =
+            storage::update_project_name(app, &project_record.id, Some(name.to_string()))?;
+    }
+
+    let base_child = storage::load_child(app, &req.project_id, &req.base_child_id)?;
+    let base_image_path = match req.base_image_path.clone() {
+        Some(path) => path,
+        None => child_image_path(&base_child, req.base_image_index)?,
+    };
+
+    let base_image_data_url = if let Some(data_url) = req.base_image_data_url.as_ref() {
+        storage::normalize_input_data_url(data_url)?
+    } else {
+        storage::read_image_path_as_data_url(Path::new(&base_image_path))?
+    };
+
+    let edit_mask = match req.mask_data_url.as_deref() {
+        Some(mask_data_url) => Some(storage::load_edit_mask(
+            &base_image_data_url,
+            mask_data_url,
+        )?),
+        None => None,
+    };
+    let seed = req.seed.or(base_child.inputs.seed);
+
+    emit_progress(app, request_id, GenerationStage::Started);
+    emit_request_sent(app, request_id, true);
+    let openrouter_response = state
+        .run_cancellable(
+            request_id,
+            state.openrouter.generate_image(GenerateImageRequest {
+                prompt: edit_prompt,
+                image_data_urls: vec![base_image_data_url],
+                mask_data_url: req.mask_data_url.clone(),
+                aspect_ratio: None,
+                resolution: req.resolution.unwrap_or(Resolution::OneK),
+                n: None,
+                seed,
+                model: req.model.clone(),
+                system_prompt: None,
+                debug: false,
+            }),
+        )
+        .await?;
+    emit_progress(app, request_id, GenerationStage::PostProcessing);
+
+    let chosen_resolution = req.resolution.unwrap_or(Resolution::OneK);
+    let chosen_data_urls = choose_best_images_for_resolution(
+        &openrouter_response.image_data_urls,
+        chosen_resolution.long_edge(),
+    );
+    let inherited_rows = base_child.inputs.rows;
+    let inherited_cols = base_child.inputs.cols;
+    let is_sprite_sheet_edit = matches!(base_child.mode, ChildMode::Sprite)
+        || matches!(
+            (inherited_rows, inherited_cols),
+            (Some(rows), Some(cols)) if rows > 1 && cols > 1
+        );
+    let child_mode = if is_sprite_sheet_edit {
+        ChildMode::Sprite
+    } else {
+        ChildMode::Edit
+    };
+    let child_id = Uuid::new_v4().to_string();
+    tracing::Span::current().record("child_id", child_id.as_str());
+    let sprite_grid = if is_sprite_sheet_edit {
+        match (inherited_rows, inherited_cols) {
+            (Some(rows), Some(cols)) if rows > 0 && cols > 0 => Some((rows, cols)),
+            _ => None,
+        }
+    } else {
+        None
+    };
+    let chromakey_color = req.chromakey_color.or(base_child.inputs.chromakey_color);
+    let chromakey_settings = req
+        .chromakey_settings
+        .or(base_child.inputs.chromakey_settings);
+    let palette_colors = req.palette_colors.or(base_child.inputs.palette_colors);
+
+    let output_options = storage::OutputImageOptions {
+        target_long_edge: None,
+        apply_chromakey: is_sprite_sheet_edit,
+        chromakey_color,
+        chromakey_settings,
+        palette_colors,
+        sprite_grid,
+        edit_mask,
+        preserved_regions: None,
+        pad_to_aspect: None,
+        measure_seams: false,
+        verify_grid: false,
+    };
+    let mut image_paths = Vec::new();
+    let mut raw_image_paths = Vec::new();
+    let mut grid_warning = None;
+    for saved in write_output_images(
+        app,
+        &project_record.id,
+        &child_id,
+        &chosen_data_urls,
+        output_options,
+    )
+    .await?
+    {
+        image_paths.push(saved.image_path);
+        raw_image_paths.extend(saved.raw_image_path);
+        grid_warning = grid_warning.or(saved.grid_warning);
+    }
+
+    let append_guard = storage::lock_child_appends();
+    let child_name = storage::next_child_name(app, &project_record.id, ChildType::Edit)
+        .inspect_err(|_| storage::discard_child_files(app, &project_record.id, &child_id))?;
+    let child = Child {
+        id: child_id,
+        project_id: project_record.id.clone(),
+        r#type: ChildType::Edit,
+        name: child_name,
+        created_at: Utc::now(),
+        mode: child_mode,
+        inputs: ChildInputs {
+            rows: if is_sprite_sheet_edit {
+                inherited_rows
+            } else {
+                None
+            },
+            cols: if is_sprite_sheet_edit {
+                inherited_cols
+            } else {
+                None
+            },
+            object_description: if is_sprite_sheet_edit {
+                base_child.inputs.object_description.clone()
+            } else {
+                None
+            },
+            style: if is_sprite_sheet_edit {
+                base_child.inputs.style.clone()
+            } else {
+                None
+            },
+            camera_angle: if is_sprite_sheet_edit {
+                base_child.inputs.camera_angle.clone()
+            } else {
+                None
+            },
+            prompt_text: if is_sprite_sheet_edit {
+                base_child.inputs.prompt_text.clone()
+            } else {
+                None
+            },
+            edit_prompt: Some(req.edit_prompt.clone()),
+            base_child_id: Some(req.base_child_id.clone()),
+            resolution: Some(chosen_resolution),
+            image_prior_data_url: None,
+            image_prior_data_urls: Vec::new(),
+            tileable: false,
+            base_image_path: Some(base_image_path),
+            mask_data_url: req.mask_data_url.clone(),
+            frame_order: if is_sprite_sheet_edit {
+                base_child.inputs.frame_order
+            } else {
+                None
+            },
+            operation: None,
+            locked_frames: None,
+            regenerated_frames: None,
+            chromakey_color: if is_sprite_sheet_edit {
+                chromakey_color
+            } else {
+                None
+            },
+            chromakey_settings: if is_sprite_sheet_edit {
+                chromakey_settings
+            } else {
+                None
+            },
+            palette_colors,
+            seed,
+            negative_prompt: None,
+            prompt_template: None,
+            style_preset: None,
+            custom_long_edge: None,
+            aspect_ratio: None,
+            model: req.model.clone(),
+            system_prompt: None,
+        },
+        openrouter: OpenRouterSnapshot {
+            model: openrouter_response.model,
+            payload: openrouter_response.sanitized_payload,
+            fallback_attempts: openrouter_response.fallback_attempts,
+        },
+        outputs: ChildOutputs {
+            text: openrouter_response.text,
+            image_paths: image_paths.clone(),
+            primary_image_path: image_paths.first().cloned(),
+            completion: openrouter_response.completion,
+            aspect_mismatch: None,
+            raw_image_paths,
+            frame_bounds: BTreeMap::new(),
+            repacked_frames: Vec::new(),
+            seamless_scores: Vec::new(),
+        },
+        notes: None,
+        label_color: None,
+    };
+
+    storage::append_child(app, &project_record.id, &child)?;
+    drop(append_guard);
+    project_record = storage::load_project_record(app, &project_record.id)?;
+    emit_progress(app, request_id, GenerationStage::Done);
+
+    Ok(ChildResult {
+        project: project_record.to_summary(),
+        child,
+        grid_warning,
+        key_color_warning: None,
+        grid_mismatch: None,
+        debug_response: None,
+    })
+}
+
+#[tauri::command]
+pub async fn regenerate_frames(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    project_id: String,
+    child_id: String,
+    keep_indices: Vec<usize>,
+    image_index: Option<usize>,
+    resolution: Option<Resolution>,
+) -> Result<ChildResult, CommandError> {
+    wrap_cmd_async("regenerate_frames", async {
+        let base_child = storage::load_child(&app, &project_id, &child_id)?;
+        if base_child.mode != ChildMode::Sprite {
+            return Err(AppError::invalid(
+                "regenerate_frames requires a sprite sheet child",
+            ));
+        }
+
+        let grid = frame_grid_or_single(&base_child);
+        let total_frames = (grid.rows * grid.cols) as usize;
+        let mut locked_frames = keep_indices;
+        locked_frames.sort_unstable();
+        locked_frames.dedup();
+        if let Some(out_of_range) = locked_frames.iter().find(|index| **index >= total_frames) {
+            return Err(AppError::invalid(format!(
+                "frame {out_of_range} is out of range for a sheet with {total_frames} frames"
+            )));
+        }
+        let regenerated_frames = (0..total_frames)
+            .filter(|index| !locked_frames.contains(index))
+            .collect::<Vec<_>>();
+        if regenerated_frames.is_empty() {
+            return Err(AppError::invalid(
+                "every frame is locked; nothing to regenerate",
+            ));
+        }
+
+        let source_path = child_image_path(&base_child, image_index)?;
+        let source_data_url = storage::read_image_path_as_data_url(Path::new(&source_path))?;
+        let preserved = storage::preserve_frames(Path::new(&source_path), grid, &locked_frames)?;
+        let resolution = resolution
+            .or(base_child.inputs.resolution)
+            .unwrap_or(Resolution::OneK);
+        let regeneration_prompt = prompt::build_frame_regeneration_prompt(
+            grid.rows,
+            grid.cols,
+            grid.order,
+            &locked_frames,
+            &regenerated_frames,
+        );
+
+        let openrouter_response = state
+            .openrouter
+            .generate_image(GenerateImageRequest {
+                prompt: regeneration_prompt.clone(),
+                image_data_urls: vec![source_data_url],
+                mask_data_url: None,
+                aspect_ratio: Some(prompt::choose_aspect_ratio(grid.cols, grid.rows).to_string()),
+                resolution,
+                n: None,
+                seed: base_child.inputs.seed,
+                model: base_child.inputs.model.clone(),
+                system_prompt: base_child.inputs.system_prompt.clone(),
+                debug: false,
+            })
+            .await?;
+
+        let chosen_data_urls = choose_best_images_for_resolution(
+            &openrouter_response.image_data_urls,
+            resolution.long_edge(),
+        );
+        let child_id = Uuid::new_v4().to_string();
+        let output_options = storage::OutputImageOptions {
+            target_long_edge: base_child.inputs.custom_long_edge,
+            apply_chromakey: true,
+            chromakey_color: base_child.inputs.chromakey_color,
+            chromakey_settings: base_child.inputs.chromakey_settings,
+            palette_colors: base_child.inputs.palette_colors,
+            sprite_grid: Some((grid.rows, grid.cols)),
+            edit_mask: None,
+            preserved_regions: Some(preserved),
+            pad_to_aspect: None,
+            measure_seams: false,
+            verify_grid: false,
+        };
+        let mut image_paths = Vec::new();
+        let mut raw_image_paths = Vec::new();
+        let mut grid_warning = None;
+        for saved in write_output_images(
+            &app,
+            &project_id,
+            &child_id,
+            &chosen_data_urls,
+            output_options,
+        )
+        .await?
+        {
+            image_paths.push(saved.image_path);
+            raw_image_paths.extend(saved.raw_image_path);
+            grid_warning = grid_warning.or(saved.grid_warning);
+        }
+
+        let mut inputs = derived_inputs(
+            &base_child,
+            &source_path,
+            "regenerate frames".to_string(),
+            true,
+        );
+        inputs.edit_prompt = Some(regeneration_prompt);
+        inputs.resolution = Some(resolution);
+        inputs.locked_frames = Some(locked_frames);
+        inputs.regenerated_frames = Some(regenerated_frames);
+        inputs.palette_colors = base_child.inputs.palette_colors;
+        inputs.seed = base_child.inputs.seed;
+        inputs.custom_long_edge = base_child.inputs.custom_long_edge;
+        inputs.model = base_child.inputs.model.clone();
+        inputs.system_prompt = base_child.inputs.system_prompt.clone();
+
+        let append_guard = storage::lock_child_appends();
+        let child_name = storage::next_child_name(&app, &project_id, ChildType::Edit)
+            .inspect_err(|_| storage::discard_child_files(&app, &project_id, &child_id))?;
+        let child = Child {
+            id: child_id,
+            project_id: project_id.clone(),
+            r#type: ChildType::Edit,
+            name: child_name,
+            created_at: Utc::now(),
+            mode: ChildMode::Sprite,
+            inputs,
+            openrouter: OpenRouterSnapshot {
+                model: openrouter_response.model,
+                payload: openrouter_response.sanitized_payload,
+                fallback_attempts: openrouter_response.fallback_attempts,
+            },
+            outputs: ChildOutputs {
+                text: openrouter_response.text,
+                image_paths: image_paths.clone(),
+                primary_image_path: image_paths.first().cloned(),
+                completion: openrouter_response.completion,
+                aspect_mismatch: None,
+                raw_image_paths,
+                frame_bounds: BTreeMap::new(),
+                repacked_frames: Vec::new(),
+                seamless_scores: Vec::new(),
+            },
+            notes: None,
+            label_color: None,
+        };
+
+        storage::append_child(&app, &project_id, &child)?;
+        drop(append_guard);
+        let project_record = storage::load_project_record(&app, &project_id)?;
+
+        Ok(ChildResult {
+            project: project_record.to_summary(),
+            child,
+            grid_warning,
+            key_color_warning: None,
+            grid_mismatch: None,
+            debug_response: None,
+        })
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn downscale_image(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+    image_index: Option<usize>,
+    target_long_edge: u32,
+    mode: DownscaleMode,
+) -> Result<ChildResult, CommandError> {
+    wrap_cmd_async("downscale_image", async move {
+        if target_long_edge == 0 {
+            return Err(AppError::invalid("targetLongEdge must be > 0"));
+        }
+
+        let base_child = storage::load_child(&app, &project_id, &child_id)?;
+        let source_path = child_image_path(&base_child, image_index)?;
+        let grid = frame_grid_or_single(&base_child);
+
+        let (image, operation) = tauri::async_runtime::spawn_blocking({
+            let source_path = source_path.clone();
+            move || -> AppResult<(RgbaImage, String)> {
+                let image = storage::load_rgba_image(Path::new(&source_path))?;
+                match mode {
+                    DownscaleMode::Exact => Ok((
+                        storage::resize_to_long_edge(&image, target_long_edge),
+                        format!("downscale to {target_long_edge}px long edge"),
+                    )),
+                    DownscaleMode::IntegerDivisor => {
+                        let factor = storage::integer_downscale_factor(
+                            image.width(),
+                            image.height(),
+                            Some((grid.rows, grid.cols)),
+                            target_long_edge,
+                        )
+                        .ok_or_else(|| {
+                            AppError::invalid(format!(
+                                "{}x{} has no integer divisor shared by every {}x{} grid cell",
+                                image.width(),
+                                image.height(),
+                                grid.cols,
+                                grid.rows
+                            ))
+                        })?;
+                        Ok((
+                            storage::box_downscale(&image, factor),
+                            format!("downscale by integer factor {factor}"),
+                        ))
+                    }
+                }
+            }
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join downscale task: {error}")))??;
+
+        let inputs = derived_inputs(&base_child, &source_path, operation, true);
+        append_derived_child(&app, &base_child, inputs, &image)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn reprocess_chromakey(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+    image_index: usize,
+    settings: ChromakeySettings,
+) -> Result<String, CommandError> {
+    wrap_cmd_async("reprocess_chromakey", async move {
+        let mut child = storage::load_child(&app, &project_id, &child_id)?;
+        child_image_path(&child, Some(image_index))?;
+
+        let sprite_grid = match (child.mode, child.inputs.rows, child.inputs.cols) {
+            (ChildMode::Sprite, Some(rows), Some(cols)) if rows > 0 && cols > 0 => {
+                Some((rows, cols))
+            }
+            _ => None,
+        };
+        let options = storage::OutputImageOptions {
+            target_long_edge: child.inputs.custom_long_edge,
+            apply_chromakey: true,
+            chromakey_color: child.inputs.chromakey_color,
+            chromakey_settings: Some(settings),
+            palette_colors: child.inputs.palette_colors,
+            sprite_grid,
+            pad_to_aspect: child
+                .outputs
+                .aspect_mismatch
+                .as_ref()
+                .filter(|mismatch| mismatch.padded)
+                .map(|mismatch| mismatch.requested),
+            ..storage::OutputImageOptions::default()
+        };
+
+        let image_path = tauri::async_runtime::spawn_blocking({
+            let app = app.clone();
+            let project_id = project_id.clone();
+            let child_id = child_id.clone();
+            let raw_image_path = child
+                .outputs
+                .raw_image_path(image_index)
+                .map(str::to_string);
+            move || {
+                storage::reprocess_output_image(
+                    &app,
+                    &project_id,
+                    &child_id,
+                    image_index,
+                    raw_image_path.as_deref(),
+                    &options,
+                )
+            }
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join reprocess task: {error}")))??;
+
+        child.inputs.chromakey_settings = Some(settings);
+        child.outputs.frame_bounds.remove(&image_index);
+        storage::save_child(&app, &child)?;
+        storage::touch_project(&app, &project_id)?;
+
+        Ok(image_path)
+    })
+    .await
+}
+
+/// Returns the tight opaque box of every frame in sheet coordinates. Results are cached on
+/// the child, so repeated calls don't re-scan the image.
+#[tauri::command]
+pub async fn compute_frame_bounds(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+    image_index: Option<usize>,
+) -> Result<Vec<Rect>, CommandError> {
+    wrap_cmd_async("compute_frame_bounds", async move {
+        let mut child = storage::load_child(&app, &project_id, &child_id)?;
+        let index = image_index.unwrap_or(0);
+        if let Some(bounds) = child.outputs.frame_bounds.get(&index) {
+            return Ok(bounds.clone());
+        }
+        let source_path = std::path::PathBuf::from(child_image_path(&child, Some(index))?);
+        let grid = frame_grid_or_single(&child);
+
+        let bounds =
+            tauri::async_runtime::spawn_blocking(move || storage::frame_bounds(&source_path, grid))
+                .await
+                .map_err(|error| {
+                    AppError::msg(format!("failed to join frame bounds task: {error}"))
+                })??;
+
+        child.outputs.frame_bounds.insert(index, bounds.clone());
+        storage::save_child(&app, &child)?;
+        Ok(bounds)
+    })
+    .await
+}
+
+const DEFAULT_RECOLOR_TOLERANCE: u8 = 8;
+
+/// The image's dominant colors with their coverage, most common first, e.g. to pick the
+/// `from` colors for `recolor_image`.
+#[tauri::command]
+pub async fn extract_palette(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+    image_index: Option<usize>,
+    max_colors: u32,
+) -> Result<Vec<PaletteSwatch>, CommandError> {
+    wrap_cmd_async("extract_palette", async move {
+        if !(1..=256).contains(&max_colors) {
+            return Err(AppError::invalid("maxColors must be between 1 and 256"));
+        }
+
+        let child = storage::load_child(&app, &project_id, &child_id)?;
+        let source_path = child_image_path(&child, image_index)?;
+
+        tauri::async_runtime::spawn_blocking(move || {
+            let image = storage::load_rgba_image(Path::new(&source_path))?;
+            Ok(storage::extract_palette(&image, max_colors))
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join palette task: {error}")))?
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn recolor_image(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+    image_index: Option<usize>,
+    mappings: Vec<ColorMapping>,
+    tolerance: Option<u8>,
+) -> Result<ChildResult, CommandError> {
+    wrap_cmd_async("recolor_image", async move {
+        if mappings.is_empty() {
+            return Err(AppError::invalid("at least one color mapping is required"));
+        }
+
+        let base_child = storage::load_child(&app, &project_id, &child_id)?;
+        let source_path = child_image_path(&base_child, image_index)?;
+        let operation = format!("recolor ({} mappings)", mappings.len());
+
+        let image = tauri::async_runtime::spawn_blocking({
+            let source_path = source_path.clone();
+            move || -> AppResult<RgbaImage> {
+                let image = storage::load_rgba_image(Path::new(&source_path))?;
+                Ok(storage::recolor(
+                    &image,
+                    &mappings,
+                    tolerance.unwrap_or(DEFAULT_RECOLOR_TOLERANCE),
+                ))
+            }
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join recolor task: {error}")))??;
+
+        let inputs = derived_inputs(&base_child, &source_path, operation, true);
+        append_derived_child(&app, &base_child, inputs, &image)
+    })
+    .await
+}
+
+const MAX_OUTLINE_THICKNESS: u32 = 16;
+
+#[tauri::command]
+pub async fn outline_image(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+    image_index: Option<usize>,
+    color: [u8; 4],
+    thickness: u32,
+    per_frame: bool,
+) -> Result<ChildResult, CommandError> {
+    wrap_cmd_async("outline_image", async move {
+        if thickness > MAX_OUTLINE_THICKNESS {
+            return Err(AppError::invalid(format!(
+                "thickness must be at most {MAX_OUTLINE_THICKNESS}"
+            )));
+        }
+
+        let base_child = storage::load_child(&app, &project_id, &child_id)?;
+        let source_path = child_image_path(&base_child, image_index)?;
+        let grid = frame_grid_or_single(&base_child);
+        let operation = format!("outline {thickness}px");
+
+        let image = tauri::async_runtime::spawn_blocking({
+            let source_path = source_path.clone();
+            move || -> AppResult<RgbaImage> {
+                let mut image = storage::load_rgba_image(Path::new(&source_path))?;
+                if per_frame {
+                    storage::add_outline_per_frame(&mut image, grid, color, thickness);
+                } else {
+                    storage::add_outline(&mut image, color, thickness);
+                }
+                Ok(image)
+            }
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join outline task: {error}")))??;
+
+        let inputs = derived_inputs(&base_child, &source_path, operation, true);
+        append_derived_child(&app, &base_child, inputs, &image)
+    })
+    .await
+}
+
+/// Cuts a rectangle out of a child's image into a new child, e.g. to use part of a result as
+/// the base for further edits. The crop doesn't follow frame boundaries, so the new child
+/// isn't treated as a sprite sheet.
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+pub async fn crop_image(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+    image_index: Option<usize>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> Result<ChildResult, CommandError> {
+    wrap_cmd_async("crop_image", async move {
+        let base_child = storage::load_child(&app, &project_id, &child_id)?;
+        let source_path = child_image_path(&base_child, image_index)?;
+        let operation = format!("crop {width}x{height} at ({x}, {y})");
+
+        let image = tauri::async_runtime::spawn_blocking({
+            let source_path = source_path.clone();
+            move || -> AppResult<RgbaImage> {
+                let image = storage::load_rgba_image(Path::new(&source_path))?;
+                storage::crop(
+                    &image,
+                    Rect {
+                        x,
+                        y,
+                        width,
+                        height,
+                    },
+                )
+            }
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join crop task: {error}")))??;
+
+        let inputs = derived_inputs(&base_child, &source_path, operation, false);
+        append_derived_child(&app, &base_child, inputs, &image)
+    })
+    .await
 }
 
+/// With `keep_frame_order`, a sprite sheet is transformed frame by frame so the sequence
+/// stays intact; a plain horizontal flip would also reverse the column order.
 #[tauri::command]
-pub async fn export_image_to_path(
-    source_image_path: String,
-    destination_path: String,
-    remove_chromakey_background: bool,
-) -> Result<String, String> {
-    wrap_cmd_async(async move {
-        let source_path = std::path::PathBuf::from(source_image_path);
-        let destination_path = std::path::PathBuf::from(destination_path);
+pub async fn transform_image(
+    app: AppHandle,
+    project_id: String,
+    child_id: String,
+    image_index: Option<usize>,
+    transform: ImageTransform,
+    keep_frame_order: bool,
+) -> Result<ChildResult, CommandError> {
+    wrap_cmd_async("transform_image", async move {
+        let base_child = storage::load_child(&app, &project_id, &child_id)?;
+        let source_path = child_image_path(&base_child, image_index)?;
+        let grid = frame_grid_or_single(&base_child);
+        let per_frame = keep_frame_order && base_child.mode == ChildMode::Sprite;
+        let operation = format!("transform {transform:?}");
 
-        tauri::async_runtime::spawn_blocking(move || {
-            storage::export_image_to_path(
-                &source_path,
-                &destination_path,
-                remove_chromakey_background,
-            )
+        let image = tauri::async_runtime::spawn_blocking({
+            let source_path = source_path.clone();
+            move || -> AppResult<RgbaImage> {
+                let mut image = storage::load_rgba_image(Path::new(&source_path))?;
+                if per_frame {
+                    storage::transform_per_frame(&mut image, grid, transform)?;
+                    Ok(image)
+                } else {
+                    Ok(storage::apply_transform(&image, transform))
+                }
+            }
         })
         .await
-        .map_err(|error| AppError::msg(format!("failed to join export task: {error}")))?
+        .map_err(|error| AppError::msg(format!("failed to join transform task: {error}")))??;
+
+        // A whole-sheet quarter turn swaps rows and columns, so the grid no longer applies.
+        let keep_grid = per_frame || !transform.swaps_axes();
+        let inputs = derived_inputs(&base_child, &source_path, operation, keep_grid);
+        append_derived_child(&app, &base_child, inputs, &image)
     })
     .await
 }
 
+/// Tiles the primary images of `child_ids`, in order, into a new sprite sheet child.
 #[tauri::command]
-pub async fn generate_image(
+pub async fn assemble_sprite_sheet(
     app: AppHandle,
-    state: State<'_, AppState>,
-    req: GenerateRequest,
-) -> Result<ChildResult, String> {
-    wrap_cmd_async(async {
-        validate_generate_request(&req)?;
-
-        if let Some(data_url) = &req.image_prior_data_url {
-            storage::validate_data_url(data_url)?;
+    project_id: String,
+    child_ids: Vec<String>,
+    cols: u32,
+    rows: u32,
+) -> Result<ChildResult, CommandError> {
+    wrap_cmd_async("assemble_sprite_sheet", async move {
+        if rows == 0 || cols == 0 {
+            return Err(AppError::invalid("rows and cols must be > 0"));
         }
-
-        let mut project_record = if let Some(project_id) = req.project_id.as_deref() {
-            storage::load_project_record(&app, project_id)?
-        } else {
-            storage::create_project_record(&app, Some(default_project_name(&req)))?
-        };
-
-        if let Some(name) = req.name.as_ref().and_then(|value| non_empty(value)) {
-            project_record =
-                storage::update_project_name(&app, &project_record.id, Some(name.to_string()))?;
+        if child_ids.is_empty() {
+            return Err(AppError::invalid("at least one child is required"));
+        }
+        let capacity = rows as usize * cols as usize;
+        if child_ids.len() > capacity {
+            return Err(AppError::invalid(format!(
+                "{} children don't fit a {rows}x{cols} grid ({capacity} cells)",
+                child_ids.len()
+            )));
         }
 
-        let child_name = storage::next_child_name(&app, &project_record.id, ChildType::Generate)?;
+        let children = child_ids
+            .iter()
+            .map(|child_id| storage::load_child(&app, &project_id, child_id))
+            .collect::<AppResult<Vec<_>>>()?;
+        let source_paths = children
+            .iter()
+            .map(|child| child_image_path(child, None))
+            .collect::<AppResult<Vec<_>>>()?;
 
-        let (mode, prompt_text, aspect_ratio) = if req.sprite_mode {
-            let rows = req
-                .rows
-                .ok_or_else(|| AppError::msg("rows is required in sprite mode"))?;
-            let cols = req
-                .cols
-                .ok_or_else(|| AppError::msg("cols is required in sprite mode"))?;
-            (
-                ChildMode::Sprite,
-                prompt::build_sprite_prompt(&req)?,
-                Some(prompt::choose_aspect_ratio(cols, rows).to_string()),
-            )
-        } else {
-            (ChildMode::Normal, prompt::build_normal_prompt(&req)?, None)
+        let sheet = tauri::async_runtime::spawn_blocking({
+            let source_paths = source_paths.clone();
+            move || -> AppResult<RgbaImage> {
+                let frames = source_paths
+                    .iter()
+                    .map(|path| storage::load_rgba_image(Path::new(path)))
+                    .collect::<AppResult<Vec<_>>>()?;
+                storage::assemble_sheet(&frames, rows, cols)
+            }
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join assemble task: {error}")))??;
+
+        let first = &children[0];
+        let inputs = ChildInputs {
+            rows: Some(rows),
+            cols: Some(cols),
+            frame_order: Some(FrameOrder::RowMajor),
+            base_child_id: Some(first.id.clone()),
+            base_image_path: source_paths.first().cloned(),
+            operation: Some(format!("assemble {} children", children.len())),
+            ..ChildInputs::default()
         };
+        append_derived_child(&app, first, inputs, &sheet)
+    })
+    .await
+}
 
-        let openrouter_response = state
-            .openrouter
-            .generate_image(GenerateImageRequest {
-                prompt: prompt_text,
-                image_data_url: req.image_prior_data_url.clone(),
-                aspect_ratio,
-                resolution: req.resolution,
-            })
-            .await?;
+const DEFAULT_CONTACT_SHEET_COLUMNS: u32 = 6;
+const MAX_CONTACT_SHEET_COLUMNS: u32 = 32;
 
-        let chosen_data_urls =
-            choose_best_images_for_resolution(&openrouter_response.image_data_urls, req.resolution);
-        let child_id = Uuid::new_v4().to_string();
-        let sprite_grid = if req.sprite_mode {
-            Some((req.rows.unwrap_or(1), req.cols.unwrap_or(1)))
-        } else {
-            None
-        };
-        let mut image_paths = Vec::new();
-        for (index, data_url) in chosen_data_urls.iter().enumerate() {
-            let image_path = storage::write_output_image(
-                &app,
-                &project_record.id,
-                &child_id,
-                index,
-                data_url,
-                req.sprite_mode,
-                sprite_grid,
-            )?;
-            image_paths.push(image_path);
+/// Renders every child's thumbnail, captioned with its name, into one PNG in the project dir
+/// for reviewing or sharing a project at a glance. Returns the PNG's path.
+#[tauri::command]
+pub async fn build_contact_sheet(
+    app: AppHandle,
+    project_id: String,
+    columns: Option<u32>,
+) -> Result<String, CommandError> {
+    wrap_cmd_async("build_contact_sheet", async move {
+        let columns = columns.unwrap_or(DEFAULT_CONTACT_SHEET_COLUMNS);
+        if !(1..=MAX_CONTACT_SHEET_COLUMNS).contains(&columns) {
+            return Err(AppError::invalid(format!(
+                "columns must be between 1 and {MAX_CONTACT_SHEET_COLUMNS}"
+            )));
         }
 
-        let child = Child {
-            id: child_id,
-            project_id: project_record.id.clone(),
-            r#type: ChildType::Generate,
-            name: child_name,
-            created_at: Utc::now(),
-            mode,
-            inputs: ChildInputs {
-                rows: req.rows,
-                cols: req.cols,
-                object_description: req.object_description.clone(),
-                style: req.style.clone(),
-                camera_angle: req.camera_angle.clone(),
-                prompt_text: req.prompt_text.clone(),
-                edit_prompt: None,
-                base_child_id: None,
-                resolution: Some(req.resolution),
-                image_prior_data_url: req.image_prior_data_url.clone(),
-                base_image_path: None,
-            },
-            openrouter: OpenRouterSnapshot {
-                model: openrouter_response.model,
-                payload: openrouter_response.sanitized_payload,
-            },
-            outputs: ChildOutputs {
-                text: openrouter_response.text,
-                image_paths: image_paths.clone(),
-                primary_image_path: image_paths.first().cloned(),
-                completion: openrouter_response.completion,
-            },
-        };
+        tauri::async_runtime::spawn_blocking(move || {
+            storage::build_contact_sheet(&app, &project_id, columns)
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join contact sheet task: {error}")))?
+    })
+    .await
+}
 
-        storage::append_child(&app, &project_record.id, &child)?;
-        project_record = storage::load_project_record(&app, &project_record.id)?;
+const DEFAULT_DIFF_THRESHOLD: u8 = 16;
 
-        Ok(ChildResult {
-            project: project_record.to_summary(),
-            child,
+/// Measures how much `child_b`'s primary image differs from `child_a`'s, e.g. to check whether
+/// an edit prompt changed anything. `threshold` is the per-channel difference a pixel must
+/// exceed to count as changed.
+#[tauri::command]
+pub async fn compare_children(
+    app: AppHandle,
+    project_id: String,
+    child_a: String,
+    child_b: String,
+    threshold: Option<u8>,
+    visualize: bool,
+) -> Result<ImageDiff, CommandError> {
+    wrap_cmd_async("compare_children", async move {
+        let path_a = child_image_path(&storage::load_child(&app, &project_id, &child_a)?, None)?;
+        let path_b = child_image_path(&storage::load_child(&app, &project_id, &child_b)?, None)?;
+
+        tauri::async_runtime::spawn_blocking(move || {
+            let image_a = storage::load_rgba_image(Path::new(&path_a))?;
+            let image_b = storage::load_rgba_image(Path::new(&path_b))?;
+            storage::diff_images(
+                &image_a,
+                &image_b,
+                threshold.unwrap_or(DEFAULT_DIFF_THRESHOLD),
+                visualize,
+            )
         })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join compare task: {error}")))?
     })
     .await
 }
 
+/// Trims every frame of a sprite sheet and packs them into a uniform grid, removing the
+/// uneven spacing models tend to leave between frames.
 #[tauri::command]
-pub async fn edit_image(
+pub async fn repack_sprite_sheet(
     app: AppHandle,
-    state: State<'_, AppState>,
-    req: EditRequest,
-) -> Result<ChildResult, String> {
-    wrap_cmd_async(async {
-        let edit_prompt = prompt::build_edit_prompt(&req.edit_prompt)?;
-
-        let mut project_record = storage::load_project_record(&app, &req.project_id)?;
-        if let Some(name) = req.name.as_ref().and_then(|value| non_empty(value)) {
-            project_record =
-                storage::update_project_name(&app, &project_record.id, Some(name.to_string()))?;
-        }
-
-        let base_child = storage::load_child(&app, &req.project_id, &req.base_child_id)?;
-        let base_image_path = req
-            .base_image_path
-            .clone()
-            .or_else(|| base_child.outputs.primary_image_path.clone())
-            .ok_or_else(|| AppError::msg("No base image path found for edit request"))?;
-
-        let base_image_data_url = if let Some(data_url) = req.base_image_data_url.as_ref() {
-            storage::validate_data_url(data_url)?;
-            data_url.clone()
-        } else {
-            storage::read_image_path_as_data_url(Path::new(&base_image_path))?
-        };
+    project_id: String,
+    child_id: String,
+    index: Option<usize>,
+) -> Result<ChildResult, CommandError> {
+    wrap_cmd_async("repack_sprite_sheet", async move {
+        let base_child = storage::load_child(&app, &project_id, &child_id)?;
+        if base_child.mode != ChildMode::Sprite {
+            return Err(AppError::invalid("only sprite sheets can be repacked"));
+        }
+        let source_path = child_image_path(&base_child, index)?;
+        let grid = frame_grid_or_single(&base_child);
 
-        let openrouter_response = state
-            .openrouter
-            .generate_image(GenerateImageRequest {
-                prompt: edit_prompt,
-                image_data_url: Some(base_image_data_url),
-                aspect_ratio: None,
-                resolution: req.resolution.unwrap_or(Resolution::OneK),
-            })
-            .await?;
+        let (sheet, repacked_frames) = tauri::async_runtime::spawn_blocking({
+            let source_path = source_path.clone();
+            move || {
+                let image = storage::load_rgba_image(Path::new(&source_path))?;
+                storage::repack_sheet(&image, grid)
+            }
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join repack task: {error}")))??;
 
-        let chosen_resolution = req.resolution.unwrap_or(Resolution::OneK);
-        let chosen_data_urls = choose_best_images_for_resolution(
-            &openrouter_response.image_data_urls,
-            chosen_resolution,
-        );
-        let inherited_rows = base_child.inputs.rows;
-        let inherited_cols = base_child.inputs.cols;
-        let is_sprite_sheet_edit = matches!(base_child.mode, ChildMode::Sprite)
-            || matches!(
-                (inherited_rows, inherited_cols),
-                (Some(rows), Some(cols)) if rows > 1 && cols > 1
-            );
-        let child_mode = if is_sprite_sheet_edit {
-            ChildMode::Sprite
-        } else {
-            ChildMode::Edit
-        };
+        let mut inputs =
+            derived_inputs(&base_child, &source_path, "repack frames".to_string(), true);
+        inputs.frame_order = Some(FrameOrder::RowMajor);
+        let mut result = append_derived_child(&app, &base_child, inputs, &sheet)?;
+        result.child.outputs.repacked_frames = repacked_frames;
+        storage::save_child(&app, &result.child)?;
+        Ok(result)
+    })
+    .await
+}
+
+/// Brings an image made elsewhere into a project as a root child, so edit chains can start
+/// from it. The file is decoded first and re-encoded as an optimized PNG.
+#[tauri::command]
+pub async fn import_image_as_child(
+    app: AppHandle,
+    project_id: String,
+    source_path: String,
+    name: Option<String>,
+) -> Result<ChildResult, CommandError> {
+    wrap_cmd_async("import_image_as_child", async move {
+        // Fail on a missing project before writing anything into it.
+        storage::load_project_record(&app, &project_id)?;
         let child_id = Uuid::new_v4().to_string();
-        let child_name = storage::next_child_name(&app, &project_record.id, ChildType::Edit)?;
-        let sprite_grid = if is_sprite_sheet_edit {
-            match (inherited_rows, inherited_cols) {
-                (Some(rows), Some(cols)) if rows > 0 && cols > 0 => Some((rows, cols)),
-                _ => None,
-            }
-        } else {
-            None
-        };
 
-        let mut image_paths = Vec::new();
-        for (index, data_url) in chosen_data_urls.iter().enumerate() {
-            let image_path = storage::write_output_image(
-                &app,
-                &project_record.id,
-                &child_id,
-                index,
-                data_url,
-                is_sprite_sheet_edit,
-                sprite_grid,
-            )?;
-            image_paths.push(image_path);
-        }
+        let image_path = tauri::async_runtime::spawn_blocking({
+            let app = app.clone();
+            let project_id = project_id.clone();
+            let child_id = child_id.clone();
+            let source_path = source_path.clone();
+            move || -> AppResult<String> {
+                let image = storage::load_rgba_image(Path::new(&source_path))?;
+                storage::save_output_image(&app, &project_id, &child_id, 0, &image)
+            }
+        })
+        .await
+        .map_err(|error| AppError::msg(format!("failed to join import task: {error}")))??;
 
+        let append_guard = storage::lock_child_appends();
+        let child_name = match name.as_deref().and_then(non_empty) {
+            Some(name) => name.to_string(),
+            None => storage::next_child_name(&app, &project_id, ChildType::Generate)
+                .inspect_err(|_| storage::discard_child_files(&app, &project_id, &child_id))?,
+        };
         let child = Child {
             id: child_id,
-            project_id: project_record.id.clone(),
-            r#type: ChildType::Edit,
+            project_id: project_id.clone(),
+            r#type: ChildType::Generate,
             name: child_name,
             created_at: Utc::now(),
-            mode: child_mode,
+            mode: ChildMode::Normal,
             inputs: ChildInputs {
-                rows: if is_sprite_sheet_edit {
-                    inherited_rows
-                } else {
-                    None
-                },
-                cols: if is_sprite_sheet_edit {
-                    inherited_cols
-                } else {
-                    None
-                },
-                object_description: if is_sprite_sheet_edit {
-                    base_child.inputs.object_description.clone()
-                } else {
-                    None
-                },
-                style: if is_sprite_sheet_edit {
-                    base_child.inputs.style.clone()
-                } else {
-                    None
-                },
-                camera_angle: if is_sprite_sheet_edit {
-                    base_child.inputs.camera_angle.clone()
-                } else {
-                    None
-                },
-                prompt_text: if is_sprite_sheet_edit {
-                    base_child.inputs.prompt_text.clone()
-                } else {
-                    None
-                },
-                edit_prompt: Some(req.edit_prompt.clone()),
-                base_child_id: Some(req.base_child_id.clone()),
-                resolution: Some(chosen_resolution),
-                image_prior_data_url: None,
-                base_image_path: Some(base_image_path),
+                base_image_path: Some(source_path),
+                operation: Some("import".to_string()),
+                ..ChildInputs::default()
             },
             openrouter: OpenRouterSnapshot {
-                model: openrouter_response.model,
-                payload: openrouter_response.sanitized_payload,
+                model: String::new(),
+                payload: serde_json::Value::Null,
+                fallback_attempts: Vec::new(),
             },
             outputs: ChildOutputs {
-                text: openrouter_response.text,
-                image_paths: image_paths.clone(),
-                primary_image_path: image_paths.first().cloned(),
-                completion: openrouter_response.completion,
+                text: None,
+                image_paths: vec![image_path.clone()],
+                primary_image_path: Some(image_path),
+                completion: None,
+                aspect_mismatch: None,
+                raw_image_paths: Vec::new(),
+                frame_bounds: BTreeMap::new(),
+                repacked_frames: Vec::new(),
+                seamless_scores: Vec::new(),
             },
+            notes: None,
+            label_color: None,
         };
 
-        storage::append_child(&app, &project_record.id, &child)?;
-        project_record = storage::load_project_record(&app, &project_record.id)?;
+        storage::append_child(&app, &project_id, &child)?;
+        drop(append_guard);
+        let project_record = storage::load_project_record(&app, &project_id)?;
 
         Ok(ChildResult {
             project: project_record.to_summary(),
             child,
+            grid_warning: None,
+            key_color_warning: None,
+            grid_mismatch: None,
+            debug_response: None,
         })
     })
     .await
 }
 
+const MAX_EXPORT_SCALE: u32 = 16;
+
+/// Checks the factor alone; the scaled size is checked against the source image in
+/// [`storage::upscale`].
+fn integer_scale_factor(factor: f64) -> AppResult<u32> {
+    if !factor.is_finite() || factor.fract() != 0.0 || factor < 1.0 {
+        return Err(AppError::invalid(format!(
+            "scaleFactor must be a whole number of at least 1 (got {factor})"
+        )));
+    }
+    if factor > MAX_EXPORT_SCALE as f64 {
+        return Err(AppError::invalid(format!(
+            "scaleFactor must be at most {MAX_EXPORT_SCALE} (got {factor})"
+        )));
+    }
+
+    Ok(factor as u32)
+}
+
+const MAX_CANDIDATES: u32 = 4;
+const MAX_REFERENCE_IMAGES: usize = 4;
+const MIN_CUSTOM_LONG_EDGE: u32 = 16;
+const MAX_CUSTOM_LONG_EDGE: u32 = 4096;
+
 fn validate_generate_request(req: &GenerateRequest) -> AppResult<()> {
     if req.sprite_mode {
         let rows = req
             .rows
-            .ok_or_else(|| AppError::msg("rows is required in sprite mode"))?;
+            .ok_or_else(|| AppError::invalid("rows is required in sprite mode"))?;
         let cols = req
             .cols
-            .ok_or_else(|| AppError::msg("cols is required in sprite mode"))?;
+            .ok_or_else(|| AppError::invalid("cols is required in sprite mode"))?;
         if rows == 0 || cols == 0 {
-            return Err(AppError::msg("rows and cols must be > 0"));
+            return Err(AppError::invalid("rows and cols must be > 0"));
         }
 
         if non_empty_opt(req.object_description.as_deref()).is_none() {
-            return Err(AppError::msg(
+            return Err(AppError::invalid(
                 "objectDescription is required in sprite mode",
             ));
         }
-        if non_empty_opt(req.style.as_deref()).is_none() {
-            return Err(AppError::msg("style is required in sprite mode"));
+        if non_empty_opt(req.style.as_deref()).is_none() && req.style_preset.is_none() {
+            return Err(AppError::invalid(
+                "style or stylePreset is required in sprite mode",
+            ));
         }
         if non_empty_opt(req.camera_angle.as_deref()).is_none() {
-            return Err(AppError::msg("cameraAngle is required in sprite mode"));
+            return Err(AppError::invalid("cameraAngle is required in sprite mode"));
+        }
+        if req.tileable {
+            return Err(AppError::invalid(
+                "tileable is only supported when spriteMode=false",
+            ));
         }
     } else if non_empty_opt(req.prompt_text.as_deref()).is_none() {
-        return Err(AppError::msg(
+        return Err(AppError::invalid(
             "promptText is required when spriteMode=false",
         ));
+    } else if req.verify_grid {
+        return Err(AppError::invalid(
+            "verifyGrid is only supported when spriteMode=true",
+        ));
+    }
+
+    if let Some(long_edge) = req.custom_long_edge {
+        if !(MIN_CUSTOM_LONG_EDGE..=MAX_CUSTOM_LONG_EDGE).contains(&long_edge) {
+            return Err(AppError::invalid(format!(
+                "customLongEdge must be between {MIN_CUSTOM_LONG_EDGE} and {MAX_CUSTOM_LONG_EDGE}"
+            )));
+        }
+    }
+
+    if let Some(key) = req.style_preset.as_deref() {
+        prompt::find_style_preset(key)?;
+    }
+
+    if req.reference_data_urls().count() > MAX_REFERENCE_IMAGES {
+        return Err(AppError::invalid(format!(
+            "at most {MAX_REFERENCE_IMAGES} reference images can be attached"
+        )));
+    }
+
+    if let Some(compression) = req.reference_compression {
+        if compression.max_long_edge < MIN_CUSTOM_LONG_EDGE {
+            return Err(AppError::invalid(format!(
+                "referenceCompression.maxLongEdge must be at least {MIN_CUSTOM_LONG_EDGE}"
+            )));
+        }
+        if compression
+            .jpeg_quality
+            .is_some_and(|quality| !(1..=100).contains(&quality))
+        {
+            return Err(AppError::invalid(
+                "referenceCompression.jpegQuality must be between 1 and 100",
+            ));
+        }
+    }
+
+    if let Some(n) = req.n {
+        if !(1..=MAX_CANDIDATES).contains(&n) {
+            return Err(AppError::invalid(format!(
+                "n must be between 1 and {MAX_CANDIDATES}"
+            )));
+        }
     }
 
     Ok(())
 }
 
+/// Builds the `base_child_id` forest in project order. Children without a base, or whose base
+/// is missing from the project, are roots. Anything left unreached (only possible with a
+/// cycle) becomes a root too, and each child appears exactly once.
+fn lineage_forest(children: &[Child]) -> Vec<ChildNode> {
+    let ids = children
+        .iter()
+        .map(|child| child.id.as_str())
+        .collect::<HashSet<_>>();
+    let mut descendants: HashMap<&str, Vec<&Child>> = HashMap::new();
+    let mut roots = Vec::new();
+    for child in children {
+        match child.inputs.base_child_id.as_deref() {
+            Some(base_id) if ids.contains(base_id) && base_id != child.id => {
+                descendants.entry(base_id).or_default().push(child);
+            }
+            _ => roots.push(child),
+        }
+    }
+
+    fn build<'a>(
+        child: &'a Child,
+        descendants: &HashMap<&str, Vec<&'a Child>>,
+        visited: &mut HashSet<&'a str>,
+    ) -> ChildNode {
+        visited.insert(&child.id);
+        let mut nodes = Vec::new();
+        for descendant in descendants.get(child.id.as_str()).into_iter().flatten() {
+            if !visited.contains(descendant.id.as_str()) {
+                nodes.push(build(descendant, descendants, visited));
+            }
+        }
+        ChildNode {
+            id: child.id.clone(),
+            name: child.name.clone(),
+            children: nodes,
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut forest = roots
+        .into_iter()
+        .map(|root| build(root, &descendants, &mut visited))
+        .collect::<Vec<_>>();
+    for child in children {
+        if !visited.contains(child.id.as_str()) {
+            forest.push(build(child, &descendants, &mut visited));
+        }
+    }
+    forest
+}
+
+/// Inputs for a child produced by a local image operation on `base_child`. Sprite grid fields
+/// carry over only when the operation keeps the frame layout intact.
+fn derived_inputs(
+    base_child: &Child,
+    source_image_path: &str,
+    operation: String,
+    keep_grid: bool,
+) -> ChildInputs {
+    let mut inputs = if keep_grid && base_child.mode == ChildMode::Sprite {
+        ChildInputs {
+            rows: base_child.inputs.rows,
+            cols: base_child.inputs.cols,
+            object_description: base_child.inputs.object_description.clone(),
+            style: base_child.inputs.style.clone(),
+            camera_angle: base_child.inputs.camera_angle.clone(),
+            frame_order: base_child.inputs.frame_order,
+            chromakey_color: base_child.inputs.chromakey_color,
+            chromakey_settings: base_child.inputs.chromakey_settings,
+            ..ChildInputs::default()
+        }
+    } else {
+        ChildInputs::default()
+    };
+
+    inputs.base_child_id = Some(base_child.id.clone());
+    inputs.base_image_path = Some(source_image_path.to_string());
+    inputs.operation = Some(operation);
+    inputs
+}
+
+fn append_derived_child(
+    app: &AppHandle,
+    base_child: &Child,
+    inputs: ChildInputs,
+    image: &RgbaImage,
+) -> AppResult<ChildResult> {
+    let project_id = base_child.project_id.clone();
+    let child_id = Uuid::new_v4().to_string();
+    let image_path = storage::save_output_image(app, &project_id, &child_id, 0, image)?;
+    let (mode, grid_warning) = match (inputs.rows, inputs.cols) {
+        (Some(rows), Some(cols)) => (
+            ChildMode::Sprite,
+            storage::validate_sprite_grid(image.width(), image.height(), rows, cols),
+        ),
+        _ => (ChildMode::Edit, None),
+    };
+
+    let append_guard = storage::lock_child_appends();
+    let child_name = storage::next_child_name(app, &project_id, ChildType::Edit)
+        .inspect_err(|_| storage::discard_child_files(app, &project_id, &child_id))?;
+    let child = Child {
+        id: child_id,
+        project_id: project_id.clone(),
+        r#type: ChildType::Edit,
+        name: child_name,
+        created_at: Utc::now(),
+        mode,
+        inputs,
+        openrouter: OpenRouterSnapshot {
+            model: String::new(),
+            payload: serde_json::Value::Null,
+            fallback_attempts: Vec::new(),
+        },
+        outputs: ChildOutputs {
+            text: None,
+            image_paths: vec![image_path.clone()],
+            primary_image_path: Some(image_path),
+            completion: None,
+            aspect_mismatch: None,
+            raw_image_paths: Vec::new(),
+            frame_bounds: BTreeMap::new(),
+            repacked_frames: Vec::new(),
+            seamless_scores: Vec::new(),
+        },
+        notes: None,
+        label_color: None,
+    };
+
+    storage::append_child(app, &project_id, &child)?;
+    drop(append_guard);
+    let project_record = storage::load_project_record(app, &project_id)?;
+
+    Ok(ChildResult {
+        project: project_record.to_summary(),
+        child,
+        grid_warning,
+        key_color_warning: None,
+        grid_mismatch: None,
+        debug_response: None,
+    })
+}
+
+fn detect_aspect_mismatch(
+    requested_ratio: &str,
+    data_url: &str,
+    pad_to_aspect: bool,
+) -> AppResult<Option<AspectMismatch>> {
+    let Some(requested) = prompt::aspect_ratio_value(requested_ratio) else {
+        return Ok(None);
+    };
+    let (width, height) = storage::data_url_dimensions(data_url)?;
+    if !storage::aspect_mismatches(width, height, requested) {
+        return Ok(None);
+    }
+
+    Ok(Some(AspectMismatch {
+        requested_ratio: requested_ratio.to_string(),
+        requested,
+        actual: width as f64 / height as f64,
+        actual_width: width,
+        actual_height: height,
+        padded: pad_to_aspect,
+    }))
+}
+
+/// `{base}.{extension}`, or with a short child id suffix (then a counter) when an earlier
+/// file in the export already took that name, so same-named children don't overwrite each
+/// other. Names are compared case-insensitively for case-insensitive filesystems.
+fn unique_export_name(
+    used: &mut HashSet<String>,
+    base: &str,
+    child_id: &str,
+    extension: &str,
+) -> String {
+    let short_id = child_id.get(..8).unwrap_or(child_id);
+    let mut candidate = format!("{base}.{extension}");
+    let mut attempt = 1;
+    while !used.insert(candidate.to_lowercase()) {
+        candidate = if attempt == 1 {
+            format!("{base}_{short_id}.{extension}")
+        } else {
+            format!("{base}_{short_id}_{attempt}.{extension}")
+        };
+        attempt += 1;
+    }
+    candidate
+}
+
+fn child_image_path(child: &Child, image_index: Option<usize>) -> AppResult<String> {
+    match image_index {
+        Some(index) => child
+            .outputs
+            .image_paths
+            .get(index)
+            .cloned()
+            .ok_or_else(|| {
+                AppError::invalid(format!(
+                    "image index {index} is out of range for child {} ({} images)",
+                    child.id,
+                    child.outputs.image_paths.len()
+                ))
+            }),
+        None => {
+            child.outputs.primary_image_path.clone().ok_or_else(|| {
+                AppError::invalid(format!("child {} has no primary image", child.id))
+            })
+        }
+    }
+}
+
+fn frame_grid_or_single(child: &Child) -> storage::FrameGrid {
+    let (rows, cols) = match (child.inputs.rows, child.inputs.cols) {
+        (Some(rows), Some(cols)) if child.mode == ChildMode::Sprite && rows > 0 && cols > 0 => {
+            (rows, cols)
+        }
+        _ => (1, 1),
+    };
+
+    storage::FrameGrid {
+        rows,
+        cols,
+        order: child.inputs.frame_order.unwrap_or_default(),
+    }
+}
+
+/// The child's grid, read in `order` when given instead of the order it was generated with.
+fn frame_grid_with_order(child: &Child, order: Option<FrameOrder>) -> storage::FrameGrid {
+    let grid = frame_grid_or_single(child);
+    storage::FrameGrid {
+        order: order.unwrap_or(grid.order),
+        ..grid
+    }
+}
+
 fn default_project_name(req: &GenerateRequest) -> String {
     let date = Utc::now().format("%m-%d-%Y");
     if req.sprite_mode {
@@ -378,6 +2589,18 @@ fn default_project_name(req: &GenerateRequest) -> String {
     }
 }
 
+fn normalize_hex_color(value: &str) -> AppResult<String> {
+    let hex = value.trim().trim_start_matches('#');
+    let valid = matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit());
+    if !valid {
+        return Err(AppError::invalid(format!(
+            "invalid color {value}. expected #RGB or #RRGGBB"
+        )));
+    }
+
+    Ok(format!("#{}", hex.to_ascii_lowercase()))
+}
+
 fn non_empty_opt(value: Option<&str>) -> Option<&str> {
     value.filter(|v| !v.trim().is_empty())
 }
@@ -390,48 +2613,154 @@ fn non_empty(value: &str) -> Option<&str> {
     }
 }
 
-fn wrap_cmd<T, F>(f: F) -> Result<T, String>
+/// Runs a command in a `command` span (recorded at debug level) and logs its failure.
+fn wrap_cmd<T, F>(command: &'static str, f: F) -> Result<T, CommandError>
 where
     F: FnOnce() -> AppResult<T>,
 {
-    f().map_err(|error| error.to_string())
+    let _entered = tracing::debug_span!("command", command).entered();
+    f().map_err(|error| command_failed(command, error))
 }
 
-async fn wrap_cmd_async<T, F>(f: F) -> Result<T, String>
+async fn wrap_cmd_async<T, F>(command: &'static str, f: F) -> Result<T, CommandError>
 where
     F: std::future::Future<Output = AppResult<T>>,
 {
-    f.await.map_err(|error| error.to_string())
+    f.instrument(tracing::debug_span!("command", command))
+        .await
+        .map_err(|error| command_failed(command, error))
+}
+
+fn command_failed(command: &'static str, error: AppError) -> CommandError {
+    match error {
+        AppError::Cancelled => tracing::info!(command, "{error}"),
+        AppError::Validation(_) => tracing::info!(command, "rejected: {error}"),
+        _ => tracing::warn!(command, kind = ?error.kind(), "{error}"),
+    }
+    CommandError::from(error)
+}
+
+/// Notes a shortfall when the model returned fewer images than were asked for.
+fn note_image_count(
+    completion: Option<CompletionMetadata>,
+    requested: u32,
+    received: usize,
+) -> Option<CompletionMetadata> {
+    if received >= requested as usize {
+        return completion;
+    }
+    let mut completion = completion.unwrap_or_default();
+    completion.image_count_note = Some(format!("requested {requested}, received {received}"));
+    Some(completion)
+}
+
+fn emit_progress(app: &AppHandle, request_id: Option<&str>, stage: GenerationStage) {
+    let progress = GenerationProgress {
+        request_id: request_id.map(str::to_string),
+        stage,
+    };
+    if let Err(error) = app.emit(stage.event_name(), progress) {
+        tracing::warn!("failed to emit {}: {error}", stage.event_name());
+    }
+}
+
+/// Validates a reference image, transcoding it when needed, and shrinks it if it's too big
+/// to send reliably.
+fn prepare_reference(data_url: &str, compression: ReferenceCompression) -> AppResult<String> {
+    let data_url = storage::normalize_input_data_url(data_url)?;
+    storage::shrink_reference_data_url(&data_url, compression)
+}
+
+fn emit_request_sent(app: &AppHandle, request_id: Option<&str>, has_image: bool) {
+    if has_image {
+        emit_progress(app, request_id, GenerationStage::UploadingImage);
+    }
+    emit_progress(app, request_id, GenerationStage::AwaitingModel);
+}
+
+/// Writes each image on its own blocking task, since keying and PNG optimization dominate
+/// at 4K and the images are independent. Results come back in input order; if any image
+/// fails, the ones already written for `child_id` are removed.
+async fn write_output_images(
+    app: &AppHandle,
+    project_id: &str,
+    child_id: &str,
+    data_urls: &[String],
+    options: storage::OutputImageOptions,
+) -> AppResult<Vec<storage::SavedOutputImage>> {
+    let options = Arc::new(options);
+    let tasks = data_urls
+        .iter()
+        .enumerate()
+        .map(|(index, data_url)| {
+            let app = app.clone();
+            let project_id = project_id.to_string();
+            let child_id = child_id.to_string();
+            let data_url = data_url.clone();
+            let options = options.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                storage::write_output_image(
+                    &app,
+                    &project_id,
+                    &child_id,
+                    index,
+                    &data_url,
+                    &options,
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut saved = Vec::with_capacity(tasks.len());
+    let mut failure = None;
+    // Every task is awaited before cleaning up, so none is still writing when files are removed.
+    for task in tasks {
+        let result = task
+            .await
+            .map_err(|error| AppError::msg(format!("failed to join output image task: {error}")))
+            .and_then(|result| result);
+        match result {
+            Ok(image) => saved.push(image),
+            Err(error) => {
+                failure.get_or_insert(error);
+            }
+        }
+    }
+    if let Some(error) = failure {
+        storage::discard_child_files(app, project_id, child_id);
+        return Err(error);
+    }
+    Ok(saved)
+}
+
+fn choose_best_images_for_resolution(data_urls: &[String], target_long_edge: u32) -> Vec<String> {
+    best_image_index_for_resolution(data_urls, target_long_edge)
+        .map(|best| vec![data_urls[best].clone()])
+        .unwrap_or_default()
 }
 
-fn choose_best_images_for_resolution(data_urls: &[String], resolution: Resolution) -> Vec<String> {
+fn best_image_index_for_resolution(data_urls: &[String], target_long_edge: u32) -> Option<usize> {
     if data_urls.len() <= 1 {
-        return data_urls.to_vec();
+        return if data_urls.is_empty() { None } else { Some(0) };
     }
 
-    let target_long_edge = resolution_long_edge(resolution);
     let mut ranked: Vec<(usize, u32, u32, u64)> = Vec::new();
 
+    // Only the header is read here; the chosen image is decoded once when it's written.
     for (index, data_url) in data_urls.iter().enumerate() {
-        let parsed = match storage::parse_data_url(data_url) {
-            Ok(parsed) => parsed,
-            Err(_) => continue,
+        let Ok((width, height)) = storage::data_url_dimensions(data_url) else {
+            continue;
         };
-        let image = match image::load_from_memory(&parsed.bytes) {
-            Ok(image) => image,
-            Err(_) => continue,
-        };
-        let (width, height) = image.dimensions();
         let long_edge = width.max(height);
         let area = width as u64 * height as u64;
         ranked.push((index, width, height, area));
         if long_edge == target_long_edge {
-            return vec![data_url.clone()];
+            return Some(index);
         }
     }
 
     if ranked.is_empty() {
-        return vec![data_urls[0].clone()];
+        return Some(0);
     }
 
     ranked.sort_by(|a, b| {
@@ -446,14 +2775,5 @@ fn choose_best_images_for_resolution(data_urls: &[String], resolution: Resolutio
             .then_with(|| a.0.cmp(&b.0))
     });
 
-    let best = ranked[0].0;
-    vec![data_urls[best].clone()]
-}
-
-fn resolution_long_edge(resolution: Resolution) -> u32 {
-    match resolution {
-        Resolution::OneK => 1024,
-        Resolution::TwoK => 2048,
-        Resolution::FourK => 4096,
-    }
+    Some(ranked[0].0)
 }
@@ -1,3 +1,4 @@
+use serde::Serialize;
 use thiserror::Error;
 
 pub type AppResult<T> = Result<T, AppError>;
@@ -6,6 +7,18 @@ pub type AppResult<T> = Result<T, AppError>;
 pub enum AppError {
     #[error("{0}")]
     Message(String),
+    #[error("{0}")]
+    Validation(String),
+    #[error("{0}")]
+    MissingApiKey(String),
+    #[error("{0}")]
+    RateLimited(String),
+    #[error("{0}")]
+    Refusal(String),
+    #[error("{0}")]
+    Timeout(String),
+    #[error("{0}")]
+    NoImages(String),
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
     #[error("serialization error: {0}")]
@@ -16,10 +29,67 @@ pub enum AppError {
     Base64(#[from] base64::DecodeError),
     #[error("image decode/encode error: {0}")]
     Image(#[from] image::ImageError),
+    #[error("archive error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("cancelled")]
+    Cancelled,
 }
 
 impl AppError {
     pub fn msg<T: Into<String>>(message: T) -> Self {
         Self::Message(message.into())
     }
+
+    /// Bad input from the caller, as opposed to something failing along the way.
+    pub fn invalid<T: Into<String>>(message: T) -> Self {
+        Self::Validation(message.into())
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Message(_) => ErrorKind::Other,
+            Self::Validation(_) => ErrorKind::Validation,
+            Self::MissingApiKey(_) => ErrorKind::MissingApiKey,
+            Self::RateLimited(_) => ErrorKind::RateLimited,
+            Self::Refusal(_) => ErrorKind::Refusal,
+            Self::NoImages(_) => ErrorKind::NoImages,
+            Self::Timeout(_) | Self::Http(_) => ErrorKind::Network,
+            Self::Io(_) | Self::Zip(_) => ErrorKind::Io,
+            Self::Serde(_) | Self::Base64(_) | Self::Image(_) => ErrorKind::Decode,
+            Self::Cancelled => ErrorKind::Cancelled,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorKind {
+    MissingApiKey,
+    Network,
+    RateLimited,
+    Refusal,
+    NoImages,
+    Validation,
+    Io,
+    Decode,
+    Cancelled,
+    Other,
+}
+
+/// What commands hand back to the frontend on failure, so the UI can react to the kind of
+/// error rather than parsing the message.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl From<AppError> for CommandError {
+    fn from(error: AppError) -> Self {
+        Self {
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
 }
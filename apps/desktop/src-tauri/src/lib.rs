@@ -1,43 +1,184 @@
 mod commands;
 mod error;
+mod logging;
 mod models;
 mod openrouter;
 mod prompt;
+mod settings;
 mod storage;
 
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use error::{AppError, AppResult};
+use models::AppSettings;
 use openrouter::{OpenRouterClient, OpenRouterConfig};
+use tauri::Manager;
+use tokio::sync::Notify;
 
 #[derive(Clone)]
 pub struct AppState {
     pub openrouter: OpenRouterClient,
+    generations: Arc<Mutex<HashMap<String, Arc<Notify>>>>,
+}
+
+impl AppState {
+    /// Runs `task` until it finishes or `cancel_generation` is called with the same request id.
+    pub async fn run_cancellable<T>(
+        &self,
+        request_id: Option<&str>,
+        task: impl Future<Output = AppResult<T>>,
+    ) -> AppResult<T> {
+        let Some(request_id) = request_id else {
+            return task.await;
+        };
+
+        let notify = Arc::new(Notify::new());
+        self.generations
+            .lock()
+            .expect("generations lock poisoned")
+            .insert(request_id.to_string(), notify.clone());
+
+        let result = tokio::select! {
+            result = task => result,
+            _ = notify.notified() => Err(AppError::Cancelled),
+        };
+
+        self.generations
+            .lock()
+            .expect("generations lock poisoned")
+            .remove(request_id);
+        result
+    }
+
+    pub fn cancel_generation(&self, request_id: &str) -> bool {
+        let generations = self.generations.lock().expect("generations lock poisoned");
+        match generations.get(request_id) {
+            Some(notify) => {
+                notify.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    load_env_files();
+    load_env_files(false);
+    logging::init();
 
     let app_state = AppState {
-        openrouter: OpenRouterClient::new(OpenRouterConfig::from_env()),
+        openrouter: OpenRouterClient::new(OpenRouterConfig::from_env(&AppSettings::default())),
+        generations: Arc::default(),
     };
 
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .manage(app_state)
+        .setup(|app| {
+            // Saved settings live in the app config dir, which needs the app to resolve.
+            let config = settings::openrouter_config(app.handle());
+            app.state::<AppState>().openrouter.replace_config(config);
+            run_startup_self_check(app.handle());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
+            commands::self_check,
+            commands::get_recent_logs,
+            commands::verify_project,
+            commands::prune_project,
+            commands::project_disk_usage,
+            commands::all_projects_disk_usage,
             commands::list_projects,
             commands::get_project,
+            commands::list_children_summary,
+            commands::reorder_children,
+            commands::get_child_lineage,
             commands::create_project,
+            commands::rename_project,
+            commands::duplicate_project,
+            commands::export_project_archive,
+            commands::import_project_archive,
+            commands::get_project_defaults,
+            commands::set_project_defaults,
+            commands::get_project_variables,
+            commands::set_project_variables,
             commands::delete_project,
+            commands::delete_child,
+            commands::update_child_annotations,
             commands::export_image_to_path,
+            commands::export_project_images,
+            commands::export_frames_to_watch_folder,
+            commands::slice_sprite_sheet,
+            commands::compute_frame_bounds,
+            commands::export_sprite_gif,
+            commands::export_atlas,
+            commands::export_sheet_at_frame_size,
+            commands::set_primary_image,
             commands::generate_image,
+            commands::preview_generation,
+            commands::list_examples,
+            commands::list_style_presets,
+            commands::list_camera_angles,
+            commands::generate_from_example,
             commands::edit_image,
+            commands::batch_generate,
+            commands::regenerate_child,
+            commands::cancel_generation,
+            commands::check_openrouter_status,
+            commands::set_openrouter_attribution,
+            commands::reload_openrouter_config,
+            commands::get_settings,
+            commands::update_settings,
+            commands::regenerate_frames,
+            commands::downscale_image,
+            commands::extract_palette,
+            commands::recolor_image,
+            commands::outline_image,
+            commands::transform_image,
+            commands::crop_image,
+            commands::import_image_as_child,
+            commands::assemble_sprite_sheet,
+            commands::repack_sprite_sheet,
+            commands::compare_children,
+            commands::build_contact_sheet,
+            commands::reprocess_chromakey,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
-fn load_env_files() {
-    let _ = dotenvy::from_filename(".env");
-    let _ = dotenvy::from_filename("../.env");
-    let _ = dotenvy::from_filename("../../.env");
+fn run_startup_self_check(app: &tauri::AppHandle) {
+    match storage::self_check(app) {
+        Ok(report) => {
+            for repair in &report.repairs {
+                tracing::info!("self-check repaired: {repair}");
+            }
+            for problem in &report.problems {
+                tracing::warn!("self-check problem: {problem}");
+            }
+        }
+        Err(error) => tracing::error!("self-check failed: {error}"),
+    }
+}
+
+const ENV_FILES: [&str; 3] = [".env", "../.env", "../../.env"];
+
+/// Loads `.env` from the working directory or up to two parents; the nearest file wins. With
+/// `override_existing`, file values replace variables already set, so edits made after launch
+/// take effect on reload.
+pub(crate) fn load_env_files(override_existing: bool) {
+    if override_existing {
+        for path in ENV_FILES.iter().rev() {
+            let _ = dotenvy::from_filename_override(path);
+        }
+    } else {
+        for path in ENV_FILES {
+            let _ = dotenvy::from_filename(path);
+        }
+    }
 }
@@ -0,0 +1,281 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    str::FromStr,
+    sync::Mutex,
+    time::Instant,
+};
+
+use chrono::Utc;
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Level, Subscriber,
+};
+use tracing_subscriber::{
+    filter::Targets,
+    layer::{Context, SubscriberExt},
+    registry::{LookupSpan, Scope},
+    Layer, Registry,
+};
+
+use crate::models::LogEntry;
+
+/// Most verbose level recorded, e.g. `debug`. Defaults to `info`.
+const LOG_LEVEL_VAR: &str = "SPRITE_DESIGNER_LOG";
+/// Entries kept for `recent_entries`; older ones are dropped first.
+const MAX_ENTRIES: usize = 500;
+const REDACTED: &str = "[redacted]";
+const API_KEY_PREFIX: &str = "sk-or-";
+/// Field names whose values are never logged.
+const SECRET_FIELD_HINTS: [&str; 4] = ["key", "token", "secret", "authorization"];
+
+static ENTRIES: Mutex<VecDeque<LogEntry>> = Mutex::new(VecDeque::new());
+
+/// Installs the collector as the global subscriber. Only this crate's events are kept, so
+/// the HTTP stack's own tracing stays out of the log.
+pub fn init() {
+    let max_level = std::env::var(LOG_LEVEL_VAR)
+        .ok()
+        .and_then(|value| Level::from_str(value.trim()).ok())
+        .unwrap_or(Level::INFO);
+    let layer =
+        LogLayer.with_filter(Targets::new().with_target(env!("CARGO_CRATE_NAME"), max_level));
+    if tracing::subscriber::set_global_default(Registry::default().with(layer)).is_err() {
+        eprintln!("a tracing subscriber was already installed; in-app logs are unavailable");
+    }
+}
+
+/// Up to `limit` of the newest entries, oldest first.
+pub fn recent_entries(limit: usize) -> Vec<LogEntry> {
+    let entries = ENTRIES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    entries
+        .iter()
+        .skip(entries.len().saturating_sub(limit))
+        .cloned()
+        .collect()
+}
+
+fn push_entry(entry: LogEntry) {
+    let fields = entry
+        .fields
+        .iter()
+        .map(|(name, value)| format!(" {name}={value}"))
+        .collect::<String>();
+    eprintln!(
+        "{} {} {}: {}{fields}",
+        entry.timestamp.format("%H:%M:%S%.3f"),
+        entry.level,
+        entry.target,
+        entry.message
+    );
+
+    let mut entries = ENTRIES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if entries.len() == MAX_ENTRIES {
+        entries.pop_front();
+    }
+    entries.push_back(entry);
+}
+
+/// Redacted fields of a span, stored in its registry extensions.
+struct SpanFields {
+    fields: Vec<(String, String)>,
+    started: Instant,
+}
+
+/// Feeds the ring buffer (and stderr) from the registry's events and span closes.
+struct LogLayer;
+
+impl LogLayer {
+    /// Fields of every span in `scope`, outermost first so inner spans win on name clashes.
+    fn scope_fields<S>(scope: Option<Scope<'_, S>>) -> BTreeMap<String, String>
+    where
+        S: for<'a> LookupSpan<'a>,
+    {
+        scope
+            .into_iter()
+            .flat_map(Scope::from_root)
+            .flat_map(|span| {
+                span.extensions()
+                    .get::<SpanFields>()
+                    .map(|data| data.fields.clone())
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+impl<S> Layer<S> for LogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attributes: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let mut fields = FieldCollector::default();
+        attributes.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanFields {
+                fields: fields.fields,
+                started: Instant::now(),
+            });
+        }
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let mut fields = FieldCollector::default();
+        values.record(&mut fields);
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(data) = extensions.get_mut::<SpanFields>() {
+            for (name, value) in fields.fields {
+                data.fields.retain(|(existing, _)| *existing != name);
+                data.fields.push((name, value));
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = FieldCollector::default();
+        event.record(&mut fields);
+        let mut all_fields = Self::scope_fields(ctx.event_scope(event));
+        all_fields.extend(fields.fields);
+        let metadata = event.metadata();
+        push_entry(LogEntry {
+            timestamp: Utc::now(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: fields.message.unwrap_or_default(),
+            fields: all_fields,
+        });
+    }
+
+    /// Logs how long the span was open once its last handle is dropped.
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let started = span
+            .extensions()
+            .get::<SpanFields>()
+            .map(|data| data.started);
+        let mut fields = Self::scope_fields(Some(span.scope()));
+        if let Some(started) = started {
+            fields.insert(
+                "elapsed_ms".to_string(),
+                started.elapsed().as_millis().to_string(),
+            );
+        }
+        let metadata = span.metadata();
+        push_entry(LogEntry {
+            timestamp: Utc::now(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: format!("{} finished", metadata.name()),
+            fields,
+        });
+    }
+}
+
+/// Collects an event's or span's fields as redacted strings, with `message` kept apart.
+#[derive(Default)]
+struct FieldCollector {
+    message: Option<String>,
+    fields: Vec<(String, String)>,
+}
+
+impl FieldCollector {
+    fn push(&mut self, field: &Field, value: String) {
+        let name = field.name();
+        if name == "message" {
+            self.message = Some(redact(&value));
+            return;
+        }
+
+        let lower = name.to_ascii_lowercase();
+        let value = if SECRET_FIELD_HINTS.iter().any(|hint| lower.contains(hint)) {
+            REDACTED.to_string()
+        } else {
+            redact(&value)
+        };
+        self.fields.push((name.to_string(), value));
+    }
+}
+
+impl Visit for FieldCollector {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.push(field, format!("{value:?}"));
+    }
+}
+
+/// Strips what must never reach a log: base64 image payloads become their size, and anything
+/// shaped like an OpenRouter key or bearer token is replaced outright.
+fn redact(text: &str) -> String {
+    let mut output = String::with_capacity(text.len().min(1024));
+    let mut rest = text;
+    while let Some(start) = rest.find("data:") {
+        output.push_str(&rest[..start]);
+        let candidate = &rest[start..];
+        let Some(marker) = candidate
+            .find(";base64,")
+            .filter(|marker| !candidate[..*marker].contains(char::is_whitespace))
+        else {
+            output.push_str("data:");
+            rest = &candidate["data:".len()..];
+            continue;
+        };
+
+        let payload_start = marker + ";base64,".len();
+        let payload_len = candidate[payload_start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=')))
+            .unwrap_or(candidate.len() - payload_start);
+        output.push_str(&candidate[..payload_start]);
+        output.push_str(&format!("[{payload_len} chars]"));
+        rest = &candidate[payload_start + payload_len..];
+    }
+    output.push_str(rest);
+
+    redact_bearer_tokens(&redact_api_keys(&output))
+}
+
+/// Every OpenRouter key becomes `[redacted]` wherever it sits: bare, quoted, or inside a
+/// query string or JSON body.
+fn redact_api_keys(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(API_KEY_PREFIX) {
+        output.push_str(&rest[..start]);
+        output.push_str(REDACTED);
+        let key = &rest[start..];
+        let key_len = key
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_')))
+            .unwrap_or(key.len());
+        rest = &key[key_len..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// `Bearer <token>` becomes `Bearer [redacted]`, whatever the token looks like.
+fn redact_bearer_tokens(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("Bearer ") {
+        let token_start = start + "Bearer ".len();
+        output.push_str(&rest[..token_start]);
+        output.push_str(REDACTED);
+        let token_len = rest[token_start..]
+            .find(|c: char| c.is_whitespace() || c == '"')
+            .unwrap_or(rest.len() - token_start);
+        rest = &rest[token_start + token_len..];
+    }
+    output.push_str(rest);
+    output
+}
@@ -1,6 +1,13 @@
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::storage::ChromakeySettings;
+
+/// Background color keyed out of sprite sheets when a request doesn't pick one.
+pub const DEFAULT_CHROMAKEY_COLOR: [u8; 3] = [0, 255, 0];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ProjectSummary {
@@ -9,6 +16,7 @@ pub struct ProjectSummary {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub child_count: usize,
+    pub thumbnail_path: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +37,25 @@ pub struct ProjectRecord {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub child_ids: Vec<String>,
+    /// Thumbnail of the most recently added child's primary image.
+    #[serde(default)]
+    pub thumbnail_path: Option<String>,
+    #[serde(default)]
+    pub defaults: Option<ChildDefaults>,
+    /// Named values `{{name}}` references in a generate request's subject text resolve to.
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+}
+
+/// Settings new generations in a project fall back to when the request leaves them out.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ChildDefaults {
+    pub style: Option<String>,
+    pub camera_angle: Option<String>,
+    pub resolution: Option<Resolution>,
+    pub rows: Option<u32>,
+    pub cols: Option<u32>,
 }
 
 impl ProjectRecord {
@@ -39,6 +66,7 @@ impl ProjectRecord {
             created_at: self.created_at,
             updated_at: self.updated_at,
             child_count: self.child_ids.len(),
+            thumbnail_path: self.thumbnail_path.clone(),
         }
     }
 }
@@ -76,6 +104,140 @@ impl Resolution {
             Self::FourK => "4K",
         }
     }
+
+    pub fn long_edge(&self) -> u32 {
+        match self {
+            Self::OneK => 1024,
+            Self::TwoK => 2048,
+            Self::FourK => 4096,
+        }
+    }
+
+    /// The smallest tier the API can generate at that still covers `long_edge`.
+    pub fn covering(long_edge: u32) -> Self {
+        [Self::OneK, Self::TwoK]
+            .into_iter()
+            .find(|tier| tier.long_edge() >= long_edge)
+            .unwrap_or(Self::FourK)
+    }
+}
+
+/// The sequence frames play in across a sprite sheet's grid. Generation prompts ask for
+/// `RowMajor`; the others let slicing and GIF export read sheets that came out differently.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum FrameOrder {
+    /// Left-to-right within a row, rows top-to-bottom.
+    #[default]
+    RowMajor,
+    /// Top-to-bottom within a column, columns left-to-right.
+    ColumnMajor,
+    /// Serpentine rows: even rows left-to-right, odd rows right-to-left.
+    BoustrophedonRows,
+    /// `RowMajor` backwards: right-to-left within a row, rows bottom-to-top, so the
+    /// bottom-right cell plays first.
+    RowMajorReversed,
+    /// `ColumnMajor` backwards: bottom-to-top within a column, columns right-to-left, so the
+    /// bottom-right cell plays first.
+    ColumnMajorReversed,
+}
+
+impl FrameOrder {
+    /// Row-major cell indices listed in play order.
+    pub fn play_order(&self, rows: u32, cols: u32) -> Vec<usize> {
+        let (rows, cols) = (rows as usize, cols as usize);
+        match self {
+            Self::RowMajor => (0..rows * cols).collect(),
+            Self::RowMajorReversed => (0..rows * cols).rev().collect(),
+            Self::ColumnMajor => (0..cols)
+                .flat_map(|col| (0..rows).map(move |row| row * cols + col))
+                .collect(),
+            Self::ColumnMajorReversed => (0..cols)
+                .rev()
+                .flat_map(|col| (0..rows).rev().map(move |row| row * cols + col))
+                .collect(),
+            Self::BoustrophedonRows => (0..rows)
+                .flat_map(|row| {
+                    let cells = (0..cols).map(move |col| row * cols + col);
+                    if row % 2 == 0 {
+                        cells.collect::<Vec<_>>()
+                    } else {
+                        cells.rev().collect::<Vec<_>>()
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DownscaleMode {
+    /// Resize so the long edge matches the target exactly.
+    Exact,
+    /// Snap to the nearest integer factor so every output pixel averages an exact NxN block.
+    IntegerDivisor,
+}
+
+/// One of an image's dominant colors and the share of its visible pixels (0.0-1.0) it covers.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PaletteSwatch {
+    pub rgb: [u8; 3],
+    pub fraction: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorMapping {
+    pub from: [u8; 3],
+    pub to: [u8; 3],
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    /// Lossless WebP; noticeably smaller than PNG for large painted sheets.
+    WebP,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::WebP => "webp",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ScaleFilter {
+    /// Hard-edged pixel duplication for pixel art.
+    #[default]
+    Nearest,
+    /// Bilinear smoothing.
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ImageTransform {
+    FlipHorizontal,
+    FlipVertical,
+    /// Clockwise.
+    Rotate90,
+    Rotate180,
+    /// Clockwise.
+    Rotate270,
+}
+
+impl ImageTransform {
+    pub fn swaps_axes(&self) -> bool {
+        matches!(self, Self::Rotate90 | Self::Rotate270)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -91,7 +253,30 @@ pub struct ChildInputs {
     pub base_child_id: Option<String>,
     pub resolution: Option<Resolution>,
     pub image_prior_data_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub image_prior_data_urls: Vec<String>,
+    #[serde(default)]
+    pub tileable: bool,
     pub base_image_path: Option<String>,
+    pub mask_data_url: Option<String>,
+    pub frame_order: Option<FrameOrder>,
+    pub operation: Option<String>,
+    pub locked_frames: Option<Vec<usize>>,
+    pub regenerated_frames: Option<Vec<usize>>,
+    pub chromakey_color: Option<[u8; 3]>,
+    pub chromakey_settings: Option<ChromakeySettings>,
+    pub palette_colors: Option<u32>,
+    pub seed: Option<u64>,
+    pub negative_prompt: Option<String>,
+    pub prompt_template: Option<String>,
+    pub style_preset: Option<String>,
+    pub custom_long_edge: Option<u32>,
+    /// Aspect ratio sent with a sprite generation, e.g. "4:3". None for normal and edit children.
+    pub aspect_ratio: Option<String>,
+    /// Model override the request asked for, if any. The model that actually answered is in
+    /// the OpenRouter snapshot.
+    pub model: Option<String>,
+    pub system_prompt: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -99,6 +284,48 @@ pub struct ChildInputs {
 pub struct OpenRouterSnapshot {
     pub model: String,
     pub payload: serde_json::Value,
+    /// Models that were tried and skipped before `model` produced the result.
+    #[serde(default)]
+    pub fallback_attempts: Vec<FallbackAttempt>,
+}
+
+/// Whether OpenRouter is usable as configured. `None` means it couldn't be checked, e.g.
+/// because the network is down or the check was skipped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigStatus {
+    pub has_key: bool,
+    pub key_valid: Option<bool>,
+    pub model_available: Option<bool>,
+    pub model: String,
+}
+
+/// Configuration saved by the app so it works without a `.env` file. Environment variables
+/// still take precedence when set. As an update, `None` keeps a field and blank clears it.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AppSettings {
+    pub api_key: Option<String>,
+    pub model: Option<String>,
+    pub referer: Option<String>,
+    pub title: Option<String>,
+}
+
+/// Saved settings as returned to the UI, with the API key reduced to whether one is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsSummary {
+    pub has_api_key: bool,
+    pub model: Option<String>,
+    pub referer: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FallbackAttempt {
+    pub model: String,
+    pub reason: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -108,6 +335,49 @@ pub struct ChildOutputs {
     pub image_paths: Vec<String>,
     pub primary_image_path: Option<String>,
     pub completion: Option<CompletionMetadata>,
+    pub aspect_mismatch: Option<AspectMismatch>,
+    /// Un-keyed copies of `image_paths`, index for index. Empty for children that were never
+    /// chromakeyed or predate raw copies.
+    #[serde(default)]
+    pub raw_image_paths: Vec<String>,
+    /// Cached opaque content box of each frame, in sheet coordinates, keyed by image index.
+    /// Cleared whenever that image is rewritten.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub frame_bounds: BTreeMap<usize, Vec<Rect>>,
+    /// Where each frame of a repacked sheet came from, in play order. Empty otherwise.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub repacked_frames: Vec<RepackedFrame>,
+    /// How seamlessly each image wraps (see `storage::seamless_score`), index for index with
+    /// `image_paths`. Only measured for tileable generations.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub seamless_scores: Vec<f64>,
+}
+
+/// A frame's trimmed content box in the source sheet and where `repack_sprite_sheet` placed
+/// it, both in sheet coordinates. `source`'s offset from its cell origin is the trim offset
+/// engines need to keep frames aligned.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RepackedFrame {
+    pub source: Rect,
+    pub target: Rect,
+}
+
+impl ChildOutputs {
+    pub fn raw_image_path(&self, index: usize) -> Option<&str> {
+        self.raw_image_paths.get(index).map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AspectMismatch {
+    pub requested_ratio: String,
+    pub requested: f64,
+    pub actual: f64,
+    pub actual_width: u32,
+    pub actual_height: u32,
+    pub padded: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -117,6 +387,9 @@ pub struct CompletionMetadata {
     pub refusal: Option<String>,
     pub reasoning: Option<String>,
     pub reasoning_details: Option<String>,
+    /// Set when fewer images came back than were requested, e.g. "requested 4, received 1".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_count_note: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -131,6 +404,22 @@ pub struct Child {
     pub inputs: ChildInputs,
     pub openrouter: OpenRouterSnapshot,
     pub outputs: ChildOutputs,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub label_color: Option<String>,
+}
+
+/// The fields a child list needs, without the inputs, payload, or outputs of a full [`Child`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChildSummary {
+    pub id: String,
+    pub name: String,
+    pub r#type: ChildType,
+    pub mode: ChildMode,
+    pub created_at: DateTime<Utc>,
+    pub primary_image_path: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -138,6 +427,264 @@ pub struct Child {
 pub struct ChildResult {
     pub project: ProjectSummary,
     pub child: Child,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grid_warning: Option<GridWarning>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_color_warning: Option<KeyColorWarning>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grid_mismatch: Option<GridMismatch>,
+    /// OpenRouter's full response with image data stripped, when the request set `debug`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug_response: Option<serde_json::Value>,
+}
+
+/// Stages reported while a generate or edit runs, each emitted as a `generation:<stage>`
+/// event. The HTTP call is a single round trip, so `UploadingImage` and `AwaitingModel` are
+/// both sent as it starts; the first only when an image is attached.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum GenerationStage {
+    Started,
+    UploadingImage,
+    AwaitingModel,
+    PostProcessing,
+    Done,
+}
+
+impl GenerationStage {
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            Self::Started => "generation:started",
+            Self::UploadingImage => "generation:uploading-image",
+            Self::AwaitingModel => "generation:awaiting-model",
+            Self::PostProcessing => "generation:post-processing",
+            Self::Done => "generation:done",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationProgress {
+    /// The id passed to `generate_image`/`edit_image`, so the UI can match events up.
+    pub request_id: Option<String>,
+    pub stage: GenerationStage,
+}
+
+/// What a generate request would send to OpenRouter, without sending it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationPreview {
+    pub prompt: String,
+    pub aspect_ratio: Option<String>,
+    pub model: String,
+    pub sanitized_payload: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_color_warning: Option<KeyColorWarning>,
+}
+
+/// Advisory, not an error: the sprite's description names a color close to the chromakey,
+/// so keying will likely eat parts of the subject.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyColorWarning {
+    /// The word in the description that triggered the warning.
+    pub keyword: String,
+    pub key_color: [u8; 3],
+    pub suggested_color: [u8; 3],
+    pub message: String,
+}
+
+/// A sprite sheet whose size isn't a whole multiple of its grid, so frames come out
+/// unevenly sized. `leftover_x`/`leftover_y` are the remainders per axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GridWarning {
+    pub width: u32,
+    pub height: u32,
+    pub rows: u32,
+    pub cols: u32,
+    pub leftover_x: u32,
+    pub leftover_y: u32,
+}
+
+/// A keyed sprite sheet whose drawn frames don't line up with the requested grid, e.g. the
+/// model drew 3 columns for a 4-column request. Detection is a heuristic over transparent
+/// gaps, so this is only a warning; the child is kept either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GridMismatch {
+    pub requested_rows: u32,
+    pub requested_cols: u32,
+    pub detected_rows: u32,
+    pub detected_cols: u32,
+}
+
+/// One child in a project's edit lineage, with the children edited from it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChildNode {
+    pub id: String,
+    pub name: String,
+    pub children: Vec<ChildNode>,
+}
+
+/// A project directory whose record couldn't be read, keyed by directory name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErroredProject {
+    pub id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectRecordScan {
+    pub projects: Vec<ProjectSummary>,
+    pub errored: Vec<ErroredProject>,
+    /// Projects on disk, regardless of the requested window.
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectList {
+    pub projects: Vec<ProjectSummary>,
+    /// Projects skipped because their project.json is unreadable.
+    pub errored: Vec<ErroredProject>,
+    /// Projects on disk, for paging; counts every project, not just this page.
+    pub total: usize,
+}
+
+/// Bytes on disk for a project (or all of them). `metadata` covers the JSON records.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskUsage {
+    pub total: u64,
+    pub images: u64,
+    pub metadata: u64,
+    pub image_count: usize,
+}
+
+/// Image files a prune removed, or would remove on a dry run.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneReport {
+    pub files: Vec<String>,
+    pub freed_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfCheckReport {
+    pub projects_checked: usize,
+    pub repairs: Vec<String>,
+    pub problems: Vec<String>,
+}
+
+/// How much two children's primary images differ, measured at the larger image's size.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageDiff {
+    pub width: u32,
+    pub height: u32,
+    /// Percent of pixels whose largest channel difference exceeds the threshold.
+    pub changed_percent: f64,
+    /// Mean absolute channel difference over every pixel and RGBA channel, 0-255.
+    pub mean_delta: f64,
+    /// PNG data URL of the first image in grayscale with changed pixels in red, when asked for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff_data_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlicedFrame {
+    pub path: String,
+    /// The frame's cell within the sheet.
+    pub cell: Rect,
+    /// The saved pixels' position within `cell`; the whole cell unless trimmed.
+    pub content: Rect,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlicedSheet {
+    pub frames: Vec<SlicedFrame>,
+    pub grid_warning: Option<GridWarning>,
+}
+
+/// A TexturePacker "JSON (Hash)" atlas, as read by Phaser and PixiJS. Frames are keyed by
+/// frame file name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Atlas {
+    pub frames: BTreeMap<String, AtlasFrame>,
+    pub meta: AtlasMeta,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AtlasFrame {
+    pub frame: AtlasRect,
+    pub rotated: bool,
+    pub trimmed: bool,
+    pub sprite_source_size: AtlasRect,
+    pub source_size: AtlasSize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AtlasRect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AtlasSize {
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AtlasMeta {
+    pub app: String,
+    pub version: String,
+    pub image: String,
+    pub format: String,
+    pub size: AtlasSize,
+    pub scale: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AtlasExport {
+    pub image_path: String,
+    pub atlas_path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectImageExport {
+    pub exported: Vec<String>,
+    pub failed: Vec<ExportFailure>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportFailure {
+    pub child_id: String,
+    pub source_path: String,
+    pub error: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -152,8 +699,82 @@ pub struct GenerateRequest {
     pub style: Option<String>,
     pub camera_angle: Option<String>,
     pub prompt_text: Option<String>,
-    pub resolution: Resolution,
+    /// Falls back to the project default, then 1K.
+    pub resolution: Option<Resolution>,
     pub image_prior_data_url: Option<String>,
+    /// Further references, such as a style or pose reference, sent after
+    /// `image_prior_data_url`.
+    #[serde(default)]
+    pub image_prior_data_urls: Vec<String>,
+    /// Ask for a texture that tiles seamlessly and measure how well it does. Normal mode only.
+    #[serde(default)]
+    pub tileable: bool,
+    /// After a sprite sheet is keyed, count the frames actually drawn and warn in
+    /// `ChildResult::grid_mismatch` when they don't match `rows`x`cols`. Sprite mode only.
+    #[serde(default)]
+    pub verify_grid: bool,
+    #[serde(default)]
+    pub pad_to_aspect: bool,
+    #[serde(default)]
+    pub frame_order: FrameOrder,
+    pub chromakey_color: Option<[u8; 3]>,
+    pub chromakey_settings: Option<ChromakeySettings>,
+    pub palette_colors: Option<u32>,
+    /// Number of candidates to request; when above 1 every returned image is kept.
+    pub n: Option<u32>,
+    pub seed: Option<u64>,
+    /// Things the model should leave out, appended to the prompt as an "Avoid:" line.
+    pub negative_prompt: Option<String>,
+    /// Replaces the built-in sprite sheet spec; see `prompt::TEMPLATE_PLACEHOLDERS`.
+    pub prompt_template: Option<String>,
+    /// Key from `prompt::STYLE_PRESETS`; its description is merged ahead of `style`.
+    pub style_preset: Option<String>,
+    /// Exact long edge in pixels for the output. The API is asked for the smallest tier that
+    /// covers it and the result is resized down locally; overrides `resolution`.
+    pub custom_long_edge: Option<u32>,
+    /// In sprite mode, bump `resolution` up a tier when needed so each frame gets a usable
+    /// pixel budget; see `effective_resolution`.
+    #[serde(default)]
+    pub scale_for_grid: bool,
+    /// OpenRouter model for this call only; blank uses the configured model.
+    pub model: Option<String>,
+    /// Standing instructions sent as a system message, separate from the built prompt.
+    pub system_prompt: Option<String>,
+    /// Return OpenRouter's full response, with image data stripped, alongside the child.
+    #[serde(default)]
+    pub debug: bool,
+    /// How a large `image_prior_data_url` is shrunk before sending; defaults when omitted.
+    pub reference_compression: Option<ReferenceCompression>,
+}
+
+impl GenerateRequest {
+    /// Every reference image in the order it's sent: `image_prior_data_url` first.
+    pub fn reference_data_urls(&self) -> impl Iterator<Item = &String> {
+        self.image_prior_data_url
+            .iter()
+            .chain(&self.image_prior_data_urls)
+    }
+}
+
+/// Shrinking applied to a reference image whose payload is too big to send reliably. Smaller
+/// references are always sent as given.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReferenceCompression {
+    /// Downscale so the long edge is at most this many pixels.
+    pub max_long_edge: u32,
+    /// Re-encode as JPEG at this quality (1-100) instead of PNG. Transparency is flattened
+    /// onto white.
+    pub jpeg_quality: Option<u8>,
+}
+
+impl Default for ReferenceCompression {
+    fn default() -> Self {
+        Self {
+            max_long_edge: 2048,
+            jpeg_quality: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -166,4 +787,27 @@ pub struct EditRequest {
     pub resolution: Option<Resolution>,
     pub base_image_data_url: Option<String>,
     pub base_image_path: Option<String>,
+    /// Which of the base child's images to edit when `base_image_path` isn't given; defaults
+    /// to its primary image.
+    pub base_image_index: Option<usize>,
+    pub mask_data_url: Option<String>,
+    pub chromakey_color: Option<[u8; 3]>,
+    pub chromakey_settings: Option<ChromakeySettings>,
+    pub palette_colors: Option<u32>,
+    /// Defaults to the base child's seed.
+    pub seed: Option<u64>,
+    /// OpenRouter model for this call only; blank uses the configured model.
+    pub model: Option<String>,
+}
+
+/// One line of the in-app log. `fields` merges the enclosing spans' fields (project and child
+/// ids, model, timing) with the event's own; secrets and image data are already redacted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    pub timestamp: DateTime<Utc>,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+    pub fields: BTreeMap<String, String>,
 }
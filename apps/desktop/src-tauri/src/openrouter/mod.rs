@@ -1,15 +1,30 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
 use reqwest::StatusCode;
 use serde::Serialize;
 use serde_json::{json, Value};
+use tracing::Instrument;
 
 use crate::{
     error::{AppError, AppResult},
-    models::{CompletionMetadata, Resolution},
+    models::{AppSettings, CompletionMetadata, ConfigStatus, FallbackAttempt, Resolution},
+    prompt,
 };
 
 const OPENROUTER_ENDPOINT: &str = "https://openrouter.ai/api/v1/chat/completions";
+const OPENROUTER_KEY_ENDPOINT: &str = "https://openrouter.ai/api/v1/key";
+const OPENROUTER_MODELS_ENDPOINT: &str = "https://openrouter.ai/api/v1/models";
+const STATUS_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
 const DEFAULT_MODEL: &str = "google/gemini-3.1-flash-image-preview";
 const DEFAULT_TITLE: &str = "Sprite Designer";
+const DEFAULT_MAX_RETRIES: u32 = 2;
+const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 500;
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+const DEFAULT_TIMEOUT_SECS: u64 = 180;
+const BODY_SNIPPET_CHARS: usize = 200;
 
 #[derive(Debug, Clone)]
 pub struct OpenRouterConfig {
@@ -17,52 +32,103 @@ pub struct OpenRouterConfig {
     pub model: String,
     pub referer: Option<String>,
     pub title: Option<String>,
+    /// Models tried in order when the primary one errors, refuses, or returns no image.
+    pub fallback_models: Vec<String>,
+    /// Extra attempts after the first for rate limits, 5xx responses, and garbled bodies.
+    pub max_retries: u32,
+    /// Backoff before the first retry; doubles on each later one.
+    pub retry_base_delay: Duration,
+    /// Total time allowed for a single request, including reading the response body.
+    pub timeout: Duration,
 }
 
 impl OpenRouterConfig {
-    pub fn from_env() -> Self {
-        let api_key = std::env::var("OPENROUTER_API_KEY")
-            .ok()
-            .filter(|v| !v.trim().is_empty());
-        let model = std::env::var("OPENROUTER_MODEL")
-            .ok()
-            .filter(|v| !v.trim().is_empty())
+    /// Reads `OPENROUTER_*` environment variables, falling back to the app's saved settings
+    /// for the key, model, referer, and title.
+    pub fn from_env(settings: &AppSettings) -> Self {
+        let api_key = env_or("OPENROUTER_API_KEY", &settings.api_key);
+        let model = env_or("OPENROUTER_MODEL", &settings.model)
             .unwrap_or_else(|| DEFAULT_MODEL.to_string());
-        let referer = std::env::var("OPENROUTER_REFERER")
-            .ok()
-            .filter(|v| !v.trim().is_empty());
-        let title = std::env::var("OPENROUTER_TITLE")
-            .ok()
-            .filter(|v| !v.trim().is_empty())
-            .or_else(|| Some(DEFAULT_TITLE.to_string()));
+        let referer = env_or("OPENROUTER_REFERER", &settings.referer);
+        let title =
+            env_or("OPENROUTER_TITLE", &settings.title).or_else(|| Some(DEFAULT_TITLE.to_string()));
+        let fallback_models = std::env::var("OPENROUTER_FALLBACK_MODELS")
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|candidate| !candidate.is_empty() && *candidate != model)
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let max_retries = env_number("OPENROUTER_MAX_RETRIES").unwrap_or(DEFAULT_MAX_RETRIES);
+        let retry_base_delay = Duration::from_millis(
+            env_number("OPENROUTER_RETRY_BASE_DELAY_MS").unwrap_or(DEFAULT_RETRY_BASE_DELAY_MS),
+        );
+        let timeout = Duration::from_secs(
+            env_number("OPENROUTER_TIMEOUT_SECS")
+                .filter(|secs| *secs > 0)
+                .unwrap_or(DEFAULT_TIMEOUT_SECS),
+        );
 
         Self {
             api_key,
             model,
             referer,
             title,
+            fallback_models,
+            max_retries,
+            retry_base_delay,
+            timeout,
         }
     }
 
     fn require_api_key(&self) -> AppResult<&str> {
         self.api_key.as_deref().ok_or_else(|| {
-            AppError::msg("OPENROUTER_API_KEY is missing. Add it to apps/desktop/.env")
+            AppError::MissingApiKey(
+                "OPENROUTER_API_KEY is missing. Add it in settings or to apps/desktop/.env"
+                    .to_string(),
+            )
         })
     }
+
+    fn request_error(&self, error: reqwest::Error) -> AppError {
+        if error.is_timeout() {
+            return AppError::Timeout(format!(
+                "OpenRouter did not respond within {}s. The provider may be busy; please try again.",
+                self.timeout.as_secs()
+            ));
+        }
+
+        error.into()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct OpenRouterClient {
     http_client: reqwest::Client,
-    config: OpenRouterConfig,
+    /// Replaced as a whole on every change; a request works from the snapshot it started
+    /// with, so an update mid-generation never mixes old and new settings.
+    config: Arc<RwLock<Arc<OpenRouterConfig>>>,
 }
 
 #[derive(Debug, Clone)]
 pub struct GenerateImageRequest {
     pub prompt: String,
-    pub image_data_url: Option<String>,
+    /// Attached after the prompt, in order.
+    pub image_data_urls: Vec<String>,
+    pub mask_data_url: Option<String>,
     pub aspect_ratio: Option<String>,
     pub resolution: Resolution,
+    pub n: Option<u32>,
+    pub seed: Option<u64>,
+    /// Overrides the configured primary model; blank falls back to it.
+    pub model: Option<String>,
+    /// Sent as a separate system message ahead of the user prompt when non-blank.
+    pub system_prompt: Option<String>,
+    /// Keep the sanitized response JSON on the result for troubleshooting.
+    pub debug: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -72,59 +138,254 @@ pub struct OpenRouterResponse {
     pub image_data_urls: Vec<String>,
     pub sanitized_payload: Value,
     pub completion: Option<CompletionMetadata>,
+    pub fallback_attempts: Vec<FallbackAttempt>,
+    /// Full response JSON with image data stripped, only when the request set `debug`.
+    pub debug_response: Option<Value>,
 }
 
 impl OpenRouterClient {
     pub fn new(config: OpenRouterConfig) -> Self {
+        let http_client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .expect("failed to build OpenRouter HTTP client");
+
         Self {
-            http_client: reqwest::Client::new(),
-            config,
+            http_client,
+            config: Arc::new(RwLock::new(Arc::new(config))),
         }
     }
 
+    fn config(&self) -> Arc<OpenRouterConfig> {
+        self.config
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    fn update_config(&self, update: impl FnOnce(&mut OpenRouterConfig)) {
+        let mut current = self
+            .config
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut next = OpenRouterConfig::clone(&current);
+        update(&mut next);
+        *current = Arc::new(next);
+    }
+
+    /// Swaps in a freshly loaded config, e.g. after the API key was added to `.env`.
+    pub fn replace_config(&self, config: OpenRouterConfig) {
+        self.update_config(|current| *current = config);
+    }
+
+    /// Sets the `HTTP-Referer` and `X-Title` headers OpenRouter attributes requests by.
+    /// Blank values clear the referer and restore the default title.
+    pub fn set_attribution(&self, referer: Option<String>, title: Option<String>) {
+        let non_blank = |value: Option<String>| {
+            value
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        self.update_config(|config| {
+            config.referer = non_blank(referer);
+            config.title = non_blank(title).or_else(|| Some(DEFAULT_TITLE.to_string()));
+        });
+    }
+
+    /// Tries the primary model, then each fallback in order, moving on when a model errors,
+    /// refuses, or returns no image. The last model's outcome is returned as-is.
+    /// Runs the request in an `openrouter` span that records the model that answered and
+    /// logs the total time, fallbacks and retries included.
     pub async fn generate_image(
         &self,
         request: GenerateImageRequest,
     ) -> AppResult<OpenRouterResponse> {
-        let payload = build_payload(&self.config.model, &request);
-        let payload_value = serde_json::to_value(&payload)?;
-        let sanitized_payload = sanitize_payload(payload_value.clone());
+        let span = tracing::info_span!(
+            "openrouter",
+            resolution = ?request.resolution,
+            references = request.image_data_urls.len(),
+            model = tracing::field::Empty,
+        );
+        self.generate_with_fallbacks(request).instrument(span).await
+    }
 
-        let mut req = self
-            .http_client
-            .post(OPENROUTER_ENDPOINT)
-            .header(
-                "Authorization",
-                format!("Bearer {}", self.config.require_api_key()?),
+    async fn generate_with_fallbacks(
+        &self,
+        request: GenerateImageRequest,
+    ) -> AppResult<OpenRouterResponse> {
+        let config = self.config();
+        config.require_api_key()?;
+
+        let mut fallback_attempts = Vec::new();
+        let primary = primary_model(&config, &request);
+        // Collected up front so no closure adapters are held across the awaits below, which
+        // would keep this future from being `Send` when spawned.
+        let models: Vec<&str> = std::iter::once(primary)
+            .chain(
+                config
+                    .fallback_models
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|candidate| *candidate != primary),
             )
-            .header("Content-Type", "application/json")
-            .json(&payload_value);
-
-        if let Some(referer) = &self.config.referer {
-            req = req.header("HTTP-Referer", referer);
+            .collect();
+        let mut models = models.into_iter().peekable();
+        while let Some(model) = models.next() {
+            let is_last = models.peek().is_none();
+            let reason = match self.generate_with_model(&config, model, &request).await {
+                Ok(response) => match fallback_reason(&response) {
+                    Some(reason) if !is_last => reason,
+                    _ => {
+                        tracing::Span::current().record("model", response.model.as_str());
+                        if let Some(debug_response) = &response.debug_response {
+                            tracing::info!(model, response = %debug_response, "OpenRouter response");
+                        }
+                        if let Some(refusal) = refusal_without_image(&response) {
+                            return Err(AppError::Refusal(format!(
+                                "{model} declined to generate an image: {refusal}"
+                            )));
+                        }
+                        if response.image_data_urls.is_empty() {
+                            return Err(no_images_error(model, response.text.as_deref()));
+                        }
+                        return Ok(OpenRouterResponse {
+                            fallback_attempts,
+                            ..response
+                        });
+                    }
+                },
+                Err(error) if !is_last => error.to_string(),
+                Err(error) => return Err(error),
+            };
+
+            tracing::warn!(
+                model,
+                "OpenRouter model failed ({reason}); trying next fallback model"
+            );
+            fallback_attempts.push(FallbackAttempt {
+                model: model.to_string(),
+                reason,
+            });
         }
 
-        if let Some(title) = &self.config.title {
-            req = req.header("X-Title", title);
+        Err(AppError::msg("no OpenRouter model is configured"))
+    }
+
+    /// Reports whether a key is configured and, when `online`, asks OpenRouter whether the key
+    /// is accepted and the configured model exists. Network trouble leaves those unknown.
+    pub async fn check_status(&self, online: bool) -> ConfigStatus {
+        let config = self.config();
+        let mut status = ConfigStatus {
+            has_key: config.api_key.is_some(),
+            key_valid: None,
+            model_available: None,
+            model: config.model.clone(),
+        };
+        if !online {
+            return status;
         }
 
-        let response = req.send().await?;
-        let status = response.status();
-        let body = response.text().await?;
+        status.key_valid = match config.api_key.as_deref() {
+            Some(api_key) => self.check_key(api_key).await,
+            None => Some(false),
+        };
+        status.model_available = self.check_model(&config.model).await;
+        status
+    }
 
-        if !status.is_success() {
-            return Err(parse_openrouter_http_error(status, &body));
+    async fn check_key(&self, api_key: &str) -> Option<bool> {
+        let response = self
+            .http_client
+            .get(OPENROUTER_KEY_ENDPOINT)
+            .bearer_auth(api_key)
+            .timeout(STATUS_CHECK_TIMEOUT)
+            .send()
+            .await
+            .ok()?;
+        match response.status() {
+            status if status.is_success() => Some(true),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Some(false),
+            _ => None,
         }
+    }
+
+    async fn check_model(&self, model: &str) -> Option<bool> {
+        let response = self
+            .http_client
+            .get(OPENROUTER_MODELS_ENDPOINT)
+            .timeout(STATUS_CHECK_TIMEOUT)
+            .send()
+            .await
+            .ok()?
+            .error_for_status()
+            .ok()?;
+        let body = response.json::<Value>().await.ok()?;
+        let models = body.get("data")?.as_array()?;
+        Some(
+            models
+                .iter()
+                .any(|entry| entry.get("id").and_then(Value::as_str) == Some(model)),
+        )
+    }
+
+    /// The primary model and the payload it would be sent, with image data stripped.
+    pub fn preview_payload(&self, request: &GenerateImageRequest) -> AppResult<(String, Value)> {
+        let config = self.config();
+        let model = primary_model(&config, request);
+        let payload = serde_json::to_value(build_payload(model, request))?;
+        Ok((model.to_string(), sanitize_payload(payload)))
+    }
+
+    async fn generate_with_model(
+        &self,
+        config: &OpenRouterConfig,
+        model: &str,
+        request: &GenerateImageRequest,
+    ) -> AppResult<OpenRouterResponse> {
+        let payload = build_payload(model, request);
+        let payload_value = serde_json::to_value(&payload)?;
+        let sanitized_payload = sanitize_payload(payload_value.clone());
+
+        let started = Instant::now();
+        let mut retries = 0;
+        let response_json = loop {
+            match self.send_once(config, &payload_value).await {
+                Ok(response_json) => break response_json,
+                Err(failure) if failure.retryable && retries < config.max_retries => {
+                    let delay = failure
+                        .retry_after
+                        .unwrap_or_else(|| backoff_delay(config.retry_base_delay, retries));
+                    retries += 1;
+                    tracing::warn!(
+                        model,
+                        retry = retries,
+                        delay_ms = delay.as_millis() as u64,
+                        "retrying OpenRouter request: {}",
+                        failure.error
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(failure) => return Err(failure.error),
+            }
+        };
 
-        let response_json: Value = serde_json::from_str(&body)?;
         let image_data_urls = extract_image_data_urls(&response_json);
+        tracing::info!(
+            model,
+            images = image_data_urls.len(),
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "OpenRouter responded"
+        );
+        let debug_response = request
+            .debug
+            .then(|| sanitize_payload(response_json.clone()));
 
         let text = extract_text(&response_json);
         let completion = extract_completion_metadata(&response_json);
         let model = response_json
             .get("model")
             .and_then(Value::as_str)
-            .unwrap_or(&self.config.model)
+            .unwrap_or(model)
             .to_string();
 
         Ok(OpenRouterResponse {
@@ -133,8 +394,193 @@ impl OpenRouterClient {
             image_data_urls,
             sanitized_payload,
             completion,
+            fallback_attempts: Vec::new(),
+            debug_response,
         })
     }
+
+    async fn send_once(
+        &self,
+        config: &OpenRouterConfig,
+        payload_value: &Value,
+    ) -> Result<Value, AttemptFailure> {
+        let api_key = config.require_api_key().map_err(AttemptFailure::fatal)?;
+        let mut req = self
+            .http_client
+            .post(OPENROUTER_ENDPOINT)
+            .header("Authorization", format!("Bearer {api_key}"))
+            .header("Content-Type", "application/json")
+            .timeout(config.timeout)
+            .json(payload_value);
+
+        if let Some(referer) = &config.referer {
+            req = req.header("HTTP-Referer", referer);
+        }
+
+        if let Some(title) = &config.title {
+            req = req.header("X-Title", title);
+        }
+
+        let response = req
+            .send()
+            .await
+            .map_err(|error| AttemptFailure::fatal(config.request_error(error)))?;
+        let status = response.status();
+        let retry_after = parse_retry_after(response.headers());
+        let body = response
+            .text()
+            .await
+            .map_err(|error| AttemptFailure::fatal(config.request_error(error)))?;
+
+        if !status.is_success() {
+            let error = parse_openrouter_http_error(status, &body);
+            return Err(if is_transient_status(status) {
+                AttemptFailure {
+                    retry_after,
+                    ..AttemptFailure::retryable(error)
+                }
+            } else {
+                AttemptFailure::fatal(error)
+            });
+        }
+
+        parse_response_body(&body)
+    }
+}
+
+/// The request's model override when non-blank, otherwise the configured model.
+fn primary_model<'a>(config: &'a OpenRouterConfig, request: &'a GenerateImageRequest) -> &'a str {
+    request
+        .model
+        .as_deref()
+        .map(str::trim)
+        .filter(|model| !model.is_empty())
+        .unwrap_or(&config.model)
+}
+
+/// A failed request attempt, flagged with whether sending it again could plausibly succeed
+/// and how long the server asked us to wait first.
+#[derive(Debug)]
+struct AttemptFailure {
+    error: AppError,
+    retryable: bool,
+    retry_after: Option<Duration>,
+}
+
+impl AttemptFailure {
+    fn fatal(error: AppError) -> Self {
+        Self {
+            error,
+            retryable: false,
+            retry_after: None,
+        }
+    }
+
+    fn retryable(error: AppError) -> Self {
+        Self {
+            error,
+            retryable: true,
+            retry_after: None,
+        }
+    }
+}
+
+fn no_images_error(model: &str, text: Option<&str>) -> AppError {
+    match text.map(str::trim).filter(|text| !text.is_empty()) {
+        Some(text) => AppError::NoImages(format!(
+            "{model} returned no images. It said: {}",
+            body_snippet(text)
+        )),
+        None => AppError::NoImages(format!("{model} returned no images")),
+    }
+}
+
+fn refusal_without_image(response: &OpenRouterResponse) -> Option<&str> {
+    if !response.image_data_urls.is_empty() {
+        return None;
+    }
+    response
+        .completion
+        .as_ref()
+        .and_then(|completion| completion.refusal.as_deref())
+}
+
+fn fallback_reason(response: &OpenRouterResponse) -> Option<String> {
+    if let Some(refusal) = response
+        .completion
+        .as_ref()
+        .and_then(|completion| completion.refusal.as_deref())
+    {
+        return Some(format!("refused: {refusal}"));
+    }
+    if response.image_data_urls.is_empty() {
+        return Some("returned no image".to_string());
+    }
+
+    None
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Only the delay-seconds form of `Retry-After` is honored; HTTP dates fall back to backoff.
+fn parse_retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(|seconds| Duration::from_secs(seconds).min(MAX_RETRY_AFTER))
+}
+
+/// `base * 2^retry` plus up to half of that again as jitter.
+fn backoff_delay(base: Duration, retry: u32) -> Duration {
+    let delay = base.saturating_mul(2_u32.saturating_pow(retry));
+    let jitter_range = (delay.as_millis() as u64 / 2).max(1);
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos() as u64)
+        .unwrap_or(0);
+    delay + Duration::from_millis(seed % jitter_range)
+}
+
+fn env_or(name: &str, fallback: &Option<String>) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .or_else(|| fallback.clone())
+}
+
+fn env_number<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok()?.trim().parse().ok()
+}
+
+/// Gateways occasionally answer 200 with a truncated or non-JSON body; those are treated as
+/// retryable and reported with a snippet of what actually came back.
+fn parse_response_body(body: &str) -> Result<Value, AttemptFailure> {
+    serde_json::from_str::<Value>(body).map_err(|error| {
+        AttemptFailure::retryable(AppError::msg(format!(
+            "OpenRouter returned an invalid JSON response ({error}): {}",
+            body_snippet(body)
+        )))
+    })
+}
+
+fn body_snippet(body: &str) -> String {
+    let trimmed = body.trim();
+    if trimmed.is_empty() {
+        return "<empty body>".to_string();
+    }
+
+    let snippet = trimmed.chars().take(BODY_SNIPPET_CHARS).collect::<String>();
+    if snippet.len() < trimmed.len() {
+        format!("{snippet}…")
+    } else {
+        snippet
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -144,6 +590,8 @@ struct ChatPayload {
     messages: Vec<ChatMessage>,
     #[serde(skip_serializing_if = "Option::is_none")]
     image_config: Option<ImageConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<u32>,
 }
 
 #[derive(Debug, Serialize)]
@@ -169,6 +617,8 @@ struct ImageConfig {
     image_size: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     aspect_ratio: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
 }
 
 fn build_payload(model: &str, request: &GenerateImageRequest) -> ChatPayload {
@@ -176,7 +626,7 @@ fn build_payload(model: &str, request: &GenerateImageRequest) -> ChatPayload {
         text: request.prompt.clone(),
     }];
 
-    if let Some(image_data_url) = &request.image_data_url {
+    for image_data_url in &request.image_data_urls {
         content.push(ContentPart::ImageUrl {
             image_url: ImageUrlPayload {
                 url: image_data_url.clone(),
@@ -184,17 +634,46 @@ fn build_payload(model: &str, request: &GenerateImageRequest) -> ChatPayload {
         });
     }
 
+    if let Some(mask_data_url) = &request.mask_data_url {
+        content.push(ContentPart::Text {
+            text: prompt::build_mask_instruction(),
+        });
+        content.push(ContentPart::ImageUrl {
+            image_url: ImageUrlPayload {
+                url: mask_data_url.clone(),
+            },
+        });
+    }
+
+    let mut messages = Vec::new();
+    if let Some(system_prompt) = request
+        .system_prompt
+        .as_deref()
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+    {
+        messages.push(ChatMessage {
+            role: "system",
+            content: vec![ContentPart::Text {
+                text: system_prompt.to_string(),
+            }],
+        });
+    }
+    messages.push(ChatMessage {
+        role: "user",
+        content,
+    });
+
     ChatPayload {
         model: model.to_string(),
         modalities: vec!["image", "text"],
-        messages: vec![ChatMessage {
-            role: "user",
-            content,
-        }],
+        messages,
         image_config: Some(ImageConfig {
             image_size: request.resolution.as_openrouter_value().to_string(),
             aspect_ratio: request.aspect_ratio.clone(),
+            seed: request.seed,
         }),
+        n: request.n,
     }
 }
 
@@ -214,9 +693,12 @@ fn parse_openrouter_http_error(status: StatusCode, body: &str) -> AppError {
         })
         .unwrap_or_else(|| body.to_string());
 
-    AppError::msg(format!(
-        "OpenRouter request failed ({status}): {openrouter_error}"
-    ))
+    let message = format!("OpenRouter request failed ({status}): {openrouter_error}");
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        AppError::RateLimited(message)
+    } else {
+        AppError::msg(message)
+    }
 }
 
 fn extract_text(response: &Value) -> Option<String> {
@@ -344,6 +826,7 @@ fn extract_completion_metadata(response: &Value) -> Option<CompletionMetadata> {
         refusal,
         reasoning,
         reasoning_details,
+        image_count_note: None,
     })
 }
 
@@ -440,3 +923,21 @@ fn sanitize_payload(payload: Value) -> Value {
     walk(&mut sanitized);
     sanitized
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncated_body_is_a_retryable_failure_with_a_snippet() {
+        let body = r#"{"choices":[{"message":{"role":"assistant","images":[{"type":"image_url""#;
+        let Err(failure) = parse_response_body(body) else {
+            panic!("a cut-off body should not parse");
+        };
+
+        assert!(failure.retryable);
+        let message = failure.error.to_string();
+        assert!(message.starts_with("OpenRouter returned an invalid JSON response"));
+        assert!(message.contains(&body_snippet(body)), "{message}");
+    }
+}
@@ -1,6 +1,11 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
 use crate::{
     error::{AppError, AppResult},
-    models::GenerateRequest,
+    models::{FrameOrder, GenerateRequest, KeyColorWarning, Resolution, DEFAULT_CHROMAKEY_COLOR},
+    storage::KeyBackground,
 };
 
 const SUPPORTED_ASPECT_RATIOS: [(&str, f64); 7] = [
@@ -13,55 +18,578 @@ const SUPPORTED_ASPECT_RATIOS: [(&str, f64); 7] = [
     ("2:3", 2.0 / 3.0),
 ];
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptExample {
+    pub id: &'static str,
+    pub title: &'static str,
+    pub rows: u32,
+    pub cols: u32,
+    pub object_description: &'static str,
+    pub style: &'static str,
+    pub camera_angle: &'static str,
+}
+
+pub const PROMPT_EXAMPLES: [PromptExample; 5] = [
+    PromptExample {
+        id: "knight-walk-cycle",
+        title: "Walk cycle",
+        rows: 1,
+        cols: 8,
+        object_description: "a knight in silver armor walking to the right, one full stride across the frames",
+        style: "16-bit pixel art, limited palette, crisp outlines",
+        camera_angle: "side view",
+    },
+    PromptExample {
+        id: "slime-idle",
+        title: "Idle loop",
+        rows: 1,
+        cols: 4,
+        object_description: "a blue slime gently bobbing up and down in place, returning to the starting pose",
+        style: "cute pixel art, soft shading",
+        camera_angle: "side view",
+    },
+    PromptExample {
+        id: "potion-icon-set",
+        title: "Icon set",
+        rows: 3,
+        cols: 3,
+        object_description: "nine different fantasy potion bottles, each with a distinct shape and liquid color",
+        style: "flat inventory icons, bold outlines, centered in each cell",
+        camera_angle: "front view",
+    },
+    PromptExample {
+        id: "wizard-turnaround",
+        title: "Character turnaround",
+        rows: 2,
+        cols: 4,
+        object_description: "a wizard in a purple robe rotating in place through eight directions, starting facing the viewer",
+        style: "hand-painted RPG character art",
+        camera_angle: "three-quarter top-down view",
+    },
+    PromptExample {
+        id: "coin-spin",
+        title: "Pickup animation",
+        rows: 1,
+        cols: 6,
+        object_description: "a gold coin spinning around its vertical axis",
+        style: "pixel art with a bright specular highlight",
+        camera_angle: "front view",
+    },
+];
+
+pub fn find_example(example_id: &str) -> AppResult<&'static PromptExample> {
+    PROMPT_EXAMPLES
+        .iter()
+        .find(|example| example.id == example_id)
+        .ok_or_else(|| {
+            let known = PROMPT_EXAMPLES
+                .iter()
+                .map(|example| example.id)
+                .collect::<Vec<_>>()
+                .join(", ");
+            AppError::invalid(format!(
+                "unknown example {example_id}. known examples: {known}"
+            ))
+        })
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StylePreset {
+    pub key: &'static str,
+    pub description: &'static str,
+}
+
+pub const STYLE_PRESETS: [StylePreset; 6] = [
+    StylePreset {
+        key: "gameboy",
+        description: "Game Boy style pixel art, strict 4-shade green palette (#0F380F, #306230, #8BAC0F, #9BBC0F), 1px outlines, no anti-aliasing, no gradients",
+    },
+    StylePreset {
+        key: "nes-8bit",
+        description: "NES-era 8-bit pixel art, at most 3 colors plus transparency per sprite, chunky pixels, no anti-aliasing",
+    },
+    StylePreset {
+        key: "snes-16bit",
+        description: "SNES-era 16-bit pixel art, rich limited palette, dark selective outlines, soft dithered shading, no anti-aliasing",
+    },
+    StylePreset {
+        key: "hand-drawn-ink",
+        description: "hand-drawn ink illustration, confident brush linework with varied weight, flat watercolor fills, subtle paper-grain texture",
+    },
+    StylePreset {
+        key: "flat-vector",
+        description: "clean flat vector art, geometric shapes, solid fills with one shade of cel shading, uniform outlines",
+    },
+    StylePreset {
+        key: "painterly",
+        description: "painterly digital illustration, visible brush strokes, soft rim lighting, rich saturated color",
+    },
+];
+
+/// Canonical camera phrasings, each with the synonyms that snap to it.
+const CAMERA_ANGLES: [(&str, &[&str]); 7] = [
+    (
+        "side view",
+        &[
+            "side",
+            "profile",
+            "side on",
+            "side scroller",
+            "side scrolling",
+            "platformer",
+        ],
+    ),
+    (
+        "front view",
+        &[
+            "front",
+            "frontal",
+            "front facing",
+            "facing camera",
+            "facing the camera",
+        ],
+    ),
+    ("back view", &["back", "rear", "rear view", "from behind"]),
+    (
+        "top-down view",
+        &[
+            "top down",
+            "top",
+            "overhead",
+            "birds eye",
+            "from above",
+            "bird view",
+        ],
+    ),
+    (
+        "three-quarter top-down view",
+        &["3/4 top down", "three quarter top down", "rpg", "jrpg"],
+    ),
+    ("isometric view", &["isometric", "iso", "2:1 isometric"]),
+    ("three-quarter view", &["3/4", "three quarter", "3 quarter"]),
+];
+
+pub fn camera_angles() -> Vec<&'static str> {
+    CAMERA_ANGLES
+        .iter()
+        .map(|(canonical, _)| *canonical)
+        .collect()
+}
+
+/// Snaps recognized synonyms ("top down", "bird's eye", "overhead view") to one canonical
+/// phrasing so a project stays consistent. Unrecognized input is returned trimmed.
+pub fn normalize_camera_angle(input: &str) -> String {
+    let comparable = |value: &str| {
+        let lowered = value
+            .to_lowercase()
+            .replace(['-', '_'], " ")
+            .replace('\'', "");
+        let words = lowered.split_whitespace().collect::<Vec<_>>().join(" ");
+        words.strip_suffix(" view").unwrap_or(&words).to_string()
+    };
+    let wanted = comparable(input);
+
+    CAMERA_ANGLES
+        .iter()
+        .find(|(canonical, synonyms)| {
+            comparable(canonical) == wanted
+                || synonyms.iter().any(|synonym| comparable(synonym) == wanted)
+        })
+        .map(|(canonical, _)| canonical.to_string())
+        .unwrap_or_else(|| input.trim().to_string())
+}
+
+pub fn find_style_preset(key: &str) -> AppResult<&'static StylePreset> {
+    STYLE_PRESETS
+        .iter()
+        .find(|preset| preset.key == key)
+        .ok_or_else(|| {
+            let known = STYLE_PRESETS
+                .iter()
+                .map(|preset| preset.key)
+                .collect::<Vec<_>>()
+                .join(", ");
+            AppError::invalid(format!(
+                "unknown style preset {key}. known presets: {known}"
+            ))
+        })
+}
+
+/// The preset's description followed by any freeform `style`, or `None` when neither is set.
+fn resolve_style(request: &GenerateRequest) -> AppResult<Option<String>> {
+    let preset = request
+        .style_preset
+        .as_deref()
+        .map(find_style_preset)
+        .transpose()?
+        .map(|preset| preset.description);
+    let freeform = request
+        .style
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty());
+
+    Ok(match (preset, freeform) {
+        (Some(preset), Some(freeform)) => Some(format!("{preset}, {freeform}")),
+        (Some(style), None) | (None, Some(style)) => Some(style.to_string()),
+        (None, None) => None,
+    })
+}
+
+impl PromptExample {
+    pub fn to_generate_request(
+        &self,
+        project_id: Option<String>,
+        resolution: Option<Resolution>,
+    ) -> GenerateRequest {
+        GenerateRequest {
+            project_id,
+            name: None,
+            sprite_mode: true,
+            rows: Some(self.rows),
+            cols: Some(self.cols),
+            object_description: Some(self.object_description.to_string()),
+            style: Some(self.style.to_string()),
+            camera_angle: Some(self.camera_angle.to_string()),
+            prompt_text: None,
+            resolution,
+            image_prior_data_url: None,
+            image_prior_data_urls: Vec::new(),
+            tileable: false,
+            verify_grid: false,
+            pad_to_aspect: false,
+            frame_order: FrameOrder::RowMajor,
+            chromakey_color: None,
+            chromakey_settings: None,
+            palette_colors: None,
+            n: None,
+            seed: None,
+            negative_prompt: None,
+            prompt_template: None,
+            style_preset: None,
+            custom_long_edge: None,
+            scale_for_grid: false,
+            model: None,
+            system_prompt: None,
+            debug: false,
+            reference_compression: None,
+        }
+    }
+}
+
 pub fn build_sprite_prompt(request: &GenerateRequest) -> AppResult<String> {
     let rows = request
         .rows
-        .ok_or_else(|| AppError::msg("rows is required in sprite mode"))?;
+        .ok_or_else(|| AppError::invalid("rows is required in sprite mode"))?;
     let cols = request
         .cols
-        .ok_or_else(|| AppError::msg("cols is required in sprite mode"))?;
+        .ok_or_else(|| AppError::invalid("cols is required in sprite mode"))?;
     let object_description = request
         .object_description
         .as_deref()
         .filter(|v| !v.trim().is_empty())
-        .ok_or_else(|| AppError::msg("objectDescription is required in sprite mode"))?;
-    let style = request
-        .style
-        .as_deref()
-        .filter(|v| !v.trim().is_empty())
-        .ok_or_else(|| AppError::msg("style is required in sprite mode"))?;
+        .ok_or_else(|| AppError::invalid("objectDescription is required in sprite mode"))?;
+    let style = resolve_style(request)?
+        .ok_or_else(|| AppError::invalid("style or stylePreset is required in sprite mode"))?;
     let camera_angle = request
         .camera_angle
         .as_deref()
         .filter(|v| !v.trim().is_empty())
-        .ok_or_else(|| AppError::msg("cameraAngle is required in sprite mode"))?;
+        .ok_or_else(|| AppError::invalid("cameraAngle is required in sprite mode"))?;
 
+    let camera_angle = normalize_camera_angle(camera_angle);
     let total_frames = rows * cols;
-    let mut prompt = format!(
-        "Sprite Sheet Spec\nFrames: {total_frames} frames total\nLayout: {cols} columns x {rows} rows\nOrder: left-to-right, top-to-bottom\nCamera: {camera_angle}; fixed camera and scale across frames\nSubject: {object_description}\nStyle: {style}\nAlignment rules: same baseline, consistent proportions, consistent lighting, even padding\nBackground: generate using a pure chromakey green background (#00FF00)\nConstraints: no text, no borders, no watermark. Generate one image file only."
-    );
+    let order = frame_order_instruction(request.frame_order);
+    let background = chromakey_background_instruction(request.chromakey_color);
+    let mut prompt = match request
+        .prompt_template
+        .as_deref()
+        .filter(|v| !v.trim().is_empty())
+    {
+        Some(template) => render_prompt_template(
+            template,
+            &[
+                ("frames", total_frames.to_string()),
+                ("cols", cols.to_string()),
+                ("rows", rows.to_string()),
+                ("order", order.to_string()),
+                ("camera", camera_angle.clone()),
+                ("subject", object_description.to_string()),
+                ("style", style.to_string()),
+                ("background", background),
+            ],
+        )?,
+        None => format!(
+            "Sprite Sheet Spec\nFrames: {total_frames} frames total\nLayout: {cols} columns x {rows} rows\nOrder: {order}\nCamera: {camera_angle}; fixed camera and scale across frames\nSubject: {object_description}\nStyle: {style}\nAlignment rules: same baseline, consistent proportions, consistent lighting, even padding\nBackground: {background}\nConstraints: no text, no borders, no watermark. Generate one image file only."
+        ),
+    };
+
+    push_negative_prompt(&mut prompt, request);
 
     if request.image_prior_data_url.is_some() {
         prompt.push_str("\nFollow the attached reference grid exactly.");
     }
+    match (
+        request.image_prior_data_url.is_some(),
+        request.image_prior_data_urls.len(),
+    ) {
+        (_, 0) => {}
+        (true, extra) => prompt.push_str(&format!(
+            "\nThe {extra} attached image(s) after the grid are references for the subject's look; match them while keeping the grid layout."
+        )),
+        (false, extra) => prompt.push_str(&format!(
+            "\nThe {extra} attached image(s) are references for the subject's look; match them."
+        )),
+    }
 
     Ok(prompt)
 }
 
+/// Placeholders a custom sprite prompt template may use. `REQUIRED_PLACEHOLDERS` must
+/// appear, since the output can't be sliced without the layout and subject.
+pub const TEMPLATE_PLACEHOLDERS: [&str; 8] = [
+    "frames",
+    "cols",
+    "rows",
+    "order",
+    "camera",
+    "subject",
+    "style",
+    "background",
+];
+const REQUIRED_PLACEHOLDERS: [&str; 3] = ["cols", "rows", "subject"];
+
+fn render_prompt_template(template: &str, values: &[(&str, String)]) -> AppResult<String> {
+    for required in REQUIRED_PLACEHOLDERS {
+        if !template.contains(&format!("{{{required}}}")) {
+            return Err(AppError::invalid(format!(
+                "promptTemplate must include {{{required}}}"
+            )));
+        }
+    }
+
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        rendered.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let close = after
+            .find('}')
+            .ok_or_else(|| AppError::invalid("promptTemplate has an unclosed '{'"))?;
+        let name = &after[..close];
+        let value = values
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| {
+                AppError::invalid(format!(
+                    "unknown placeholder {{{name}}} in promptTemplate. known placeholders: {}",
+                    TEMPLATE_PLACEHOLDERS.join(", ")
+                ))
+            })?;
+        rendered.push_str(value);
+        rest = &after[close + 1..];
+    }
+    rendered.push_str(rest);
+
+    Ok(rendered)
+}
+
+/// Checks project variable names before they're stored: letters, digits, `_` and `-`.
+pub fn validate_variables(variables: &BTreeMap<String, String>) -> AppResult<()> {
+    for name in variables.keys() {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(AppError::invalid(format!(
+                "invalid variable name '{name}'. use letters, digits, '_' or '-'"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Replaces `{{name}}` references with the project's variables. A reference to a name the
+/// project doesn't define is an error rather than being sent to the model verbatim.
+pub fn resolve_variables(text: &str, variables: &BTreeMap<String, String>) -> AppResult<String> {
+    let mut resolved = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find("{{") {
+        resolved.push_str(&rest[..open]);
+        let after = &rest[open + 2..];
+        let close = after
+            .find("}}")
+            .ok_or_else(|| AppError::invalid("prompt has an unclosed '{{'"))?;
+        let name = after[..close].trim();
+        let value = variables
+            .get(name)
+            .ok_or_else(|| AppError::invalid(format!("unknown prompt variable {{{{{name}}}}}")))?;
+        resolved.push_str(value);
+        rest = &after[close + 2..];
+    }
+    resolved.push_str(rest);
+
+    Ok(resolved)
+}
+
+/// Resolves variables in the request's subject text, before the prompt is built.
+pub fn apply_variables(
+    request: &mut GenerateRequest,
+    variables: &BTreeMap<String, String>,
+) -> AppResult<()> {
+    for text in [&mut request.object_description, &mut request.prompt_text]
+        .into_iter()
+        .flatten()
+    {
+        *text = resolve_variables(text, variables)?;
+    }
+    Ok(())
+}
+
+fn push_negative_prompt(prompt: &mut String, request: &GenerateRequest) {
+    if let Some(avoid) = request
+        .negative_prompt
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+    {
+        prompt.push_str("\nAvoid: ");
+        prompt.push_str(avoid);
+    }
+}
+
+pub fn frame_order_instruction(order: FrameOrder) -> &'static str {
+    match order {
+        FrameOrder::RowMajor => "left-to-right, top-to-bottom",
+        FrameOrder::ColumnMajor => {
+            "top-to-bottom, left-to-right (fill each column before the next)"
+        }
+        FrameOrder::BoustrophedonRows => {
+            "serpentine rows: first row left-to-right, next row right-to-left, alternating"
+        }
+        FrameOrder::RowMajorReversed => {
+            "right-to-left, bottom-to-top (the first frame is in the bottom-right cell)"
+        }
+        FrameOrder::ColumnMajorReversed => {
+            "bottom-to-top, right-to-left (fill each column upward, rightmost column first)"
+        }
+    }
+}
+
+fn chromakey_background_instruction(color: Option<[u8; 3]>) -> String {
+    let rgb = color.unwrap_or(DEFAULT_CHROMAKEY_COLOR);
+    let hex = format!("#{:02X}{:02X}{:02X}", rgb[0], rgb[1], rgb[2]);
+    if rgb == DEFAULT_CHROMAKEY_COLOR {
+        format!("generate using a pure chromakey green background ({hex})")
+    } else {
+        format!("generate using a pure solid chromakey background ({hex}); do not use this color anywhere in the subject")
+    }
+}
+
+/// Words that suggest a subject shares a key's hue, by the key's dominant channels
+/// (red, green, blue at or above 128).
+const KEY_COLOR_KEYWORDS: [([bool; 3], &[&str]); 3] = [
+    (
+        [false, true, false],
+        &[
+            "green", "greenish", "emerald", "lime", "jade", "olive", "mint", "slime", "leaf",
+            "leaves", "grass", "moss", "frog", "cactus", "goblin", "zombie",
+        ],
+    ),
+    (
+        [true, false, true],
+        &["magenta", "pink", "fuchsia", "purple", "violet"],
+    ),
+    (
+        [false, false, true],
+        &["blue", "bluish", "azure", "navy", "cobalt", "sapphire"],
+    ),
+];
+/// Suggested instead of a green key whose hue the subject shares.
+const ALTERNATE_CHROMAKEY_COLOR: [u8; 3] = [255, 0, 255];
+
+/// Checks a sprite request's subject and style for words naming the key's hue. Only solid
+/// color keys are checked; white, checkerboard, and auto-detected keys are left alone.
+pub fn key_color_conflict(request: &GenerateRequest) -> Option<KeyColorWarning> {
+    let settings = request.chromakey_settings.unwrap_or_default();
+    if !request.sprite_mode
+        || settings.background != KeyBackground::Color
+        || settings.auto_detect_color
+    {
+        return None;
+    }
+
+    let key_color = request.chromakey_color.unwrap_or(DEFAULT_CHROMAKEY_COLOR);
+    let dominant = key_color.map(|channel| channel >= 128);
+    let (_, keywords) = KEY_COLOR_KEYWORDS
+        .iter()
+        .find(|(channels, _)| *channels == dominant)?;
+    let keyword = [
+        request.object_description.as_deref(),
+        request.style.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .flat_map(|text| text.split(|c: char| !c.is_alphanumeric()))
+    .map(str::to_lowercase)
+    .find(|word| {
+        let singular = word.strip_suffix('s').unwrap_or(word);
+        keywords.contains(&word.as_str()) || keywords.contains(&singular)
+    })?;
+
+    let suggested_color = if dominant == [false, true, false] {
+        ALTERNATE_CHROMAKEY_COLOR
+    } else {
+        DEFAULT_CHROMAKEY_COLOR
+    };
+    let hex = |rgb: [u8; 3]| format!("#{:02X}{:02X}{:02X}", rgb[0], rgb[1], rgb[2]);
+    Some(KeyColorWarning {
+        message: format!(
+            "\"{keyword}\" suggests the subject shares the chromakey color {}; keying may erase parts of it. Consider chromakeyColor {} instead.",
+            hex(key_color),
+            hex(suggested_color),
+        ),
+        keyword,
+        key_color,
+        suggested_color,
+    })
+}
+
 pub fn build_normal_prompt(request: &GenerateRequest) -> AppResult<String> {
     let prompt = request
         .prompt_text
         .as_deref()
         .filter(|v| !v.trim().is_empty())
-        .ok_or_else(|| AppError::msg("promptText is required when spriteMode=false"))?;
+        .ok_or_else(|| AppError::invalid("promptText is required when spriteMode=false"))?;
 
-    Ok(prompt.to_string())
+    let mut prompt = prompt.to_string();
+    if request.style_preset.is_some() {
+        if let Some(style) = resolve_style(request)? {
+            prompt.push_str("\nStyle: ");
+            prompt.push_str(&style);
+        }
+    }
+    if request.tileable {
+        prompt.push_str("\nTiling: make this a seamless repeating texture. The left edge must continue into the right edge and the top into the bottom; no borders, frames, vignettes, or features cut off at the edges.");
+    }
+    push_negative_prompt(&mut prompt, request);
+    let references = request.reference_data_urls().count();
+    if references > 1 {
+        prompt.push_str(&format!(
+            "\n{references} reference images are attached, in order."
+        ));
+    }
+    Ok(prompt)
 }
 
 pub fn build_edit_prompt(edit_prompt: &str) -> AppResult<String> {
     let trimmed = edit_prompt.trim();
     if trimmed.is_empty() {
-        return Err(AppError::msg("editPrompt is required"));
+        return Err(AppError::invalid("editPrompt is required"));
     }
 
     Ok(format!(
@@ -69,6 +597,45 @@ pub fn build_edit_prompt(edit_prompt: &str) -> AppResult<String> {
     ))
 }
 
+pub fn build_frame_regeneration_prompt(
+    rows: u32,
+    cols: u32,
+    order: FrameOrder,
+    locked_frames: &[usize],
+    regenerated_frames: &[usize],
+) -> String {
+    let frame_list = |frames: &[usize]| {
+        frames
+            .iter()
+            .map(|frame| (frame + 1).to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let order = frame_order_instruction(order);
+    let locked = frame_list(locked_frames);
+    let regenerated = frame_list(regenerated_frames);
+
+    format!(
+        "The attached image is a sprite sheet of {cols} columns x {rows} rows, frames numbered 1 to {} in this order: {order}.\nKeep frames {locked} exactly as they are.\nRedraw frames {regenerated} so they match the kept frames in subject, proportions, style, lighting, and baseline, and read as a smooth sequence with them.\nKeep the same grid layout, cell size, and chromakey background. No text, no borders, no watermark.",
+        rows * cols
+    )
+}
+
+pub fn build_mask_instruction() -> String {
+    "The next image is an edit mask aligned with the base image. Only change the regions that are white in the mask; keep everything under the black regions exactly as it is.".to_string()
+}
+
+pub fn aspect_ratio_value(ratio: &str) -> Option<f64> {
+    let (width, height) = ratio.split_once(':')?;
+    let width = width.trim().parse::<f64>().ok()?;
+    let height = height.trim().parse::<f64>().ok()?;
+    if width <= 0.0 || height <= 0.0 {
+        return None;
+    }
+
+    Some(width / height)
+}
+
 pub fn choose_aspect_ratio(cols: u32, rows: u32) -> &'static str {
     if rows == 0 || cols == 0 {
         return "1:1";
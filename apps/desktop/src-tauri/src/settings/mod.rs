@@ -0,0 +1,113 @@
+use std::path::PathBuf;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    error::{AppError, AppResult},
+    models::{AppSettings, SettingsSummary},
+    openrouter::OpenRouterConfig,
+    storage,
+};
+
+const SETTINGS_FILE: &str = "settings.json";
+/// XOR pad for the stored API key. This only keeps the key from being readable at a glance
+/// in the file; anyone with access to the file and this source can recover it.
+const OBFUSCATION_PAD: &[u8] = b"sprite-designer/settings";
+
+/// On-disk form of [`AppSettings`]; `api_key` holds the obfuscated key.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct StoredSettings {
+    api_key: Option<String>,
+    model: Option<String>,
+    referer: Option<String>,
+    title: Option<String>,
+}
+
+/// Settings saved in the app config dir. A missing file means nothing has been saved yet.
+pub fn load_settings(app: &AppHandle) -> AppResult<AppSettings> {
+    let path = settings_path(app)?;
+    if !path.exists() {
+        return Ok(AppSettings::default());
+    }
+
+    let stored: StoredSettings = storage::read_json(&path)?;
+    Ok(AppSettings {
+        api_key: stored.api_key.as_deref().map(reveal).transpose()?,
+        model: stored.model,
+        referer: stored.referer,
+        title: stored.title,
+    })
+}
+
+/// Applies `update` on top of the saved settings: `None` keeps a field, a blank value clears
+/// it, anything else replaces it.
+pub fn update_settings(app: &AppHandle, update: AppSettings) -> AppResult<AppSettings> {
+    let current = load_settings(app)?;
+    let merge = |current: Option<String>, update: Option<String>| match update {
+        Some(value) => Some(value.trim().to_string()).filter(|value| !value.is_empty()),
+        None => current,
+    };
+    let settings = AppSettings {
+        api_key: merge(current.api_key, update.api_key),
+        model: merge(current.model, update.model),
+        referer: merge(current.referer, update.referer),
+        title: merge(current.title, update.title),
+    };
+
+    let stored = StoredSettings {
+        api_key: settings.api_key.as_deref().map(obfuscate),
+        model: settings.model.clone(),
+        referer: settings.referer.clone(),
+        title: settings.title.clone(),
+    };
+    storage::write_json(&settings_path(app)?, &stored)?;
+    Ok(settings)
+}
+
+/// What the UI may show: whether a key is saved, never the key itself.
+pub fn summarize(settings: &AppSettings) -> SettingsSummary {
+    SettingsSummary {
+        has_api_key: settings.api_key.is_some(),
+        model: settings.model.clone(),
+        referer: settings.referer.clone(),
+        title: settings.title.clone(),
+    }
+}
+
+/// Environment variables layered over the saved settings. Unreadable settings are logged
+/// and skipped so a corrupt file can't keep a `.env` setup from working.
+pub fn openrouter_config(app: &AppHandle) -> OpenRouterConfig {
+    let settings = load_settings(app).unwrap_or_else(|error| {
+        tracing::warn!("failed to load settings: {error}");
+        AppSettings::default()
+    });
+    OpenRouterConfig::from_env(&settings)
+}
+
+fn settings_path(app: &AppHandle) -> AppResult<PathBuf> {
+    Ok(app
+        .path()
+        .app_config_dir()
+        .map_err(|error| AppError::msg(format!("failed to resolve app config dir: {error}")))?
+        .join(SETTINGS_FILE))
+}
+
+fn xor_pad(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .zip(OBFUSCATION_PAD.iter().cycle())
+        .map(|(byte, pad)| byte ^ pad)
+        .collect()
+}
+
+fn obfuscate(api_key: &str) -> String {
+    STANDARD.encode(xor_pad(api_key.as_bytes()))
+}
+
+fn reveal(stored: &str) -> AppResult<String> {
+    String::from_utf8(xor_pad(&STANDARD.decode(stored)?))
+        .map_err(|_| AppError::msg("stored API key is corrupt; enter it again in settings"))
+}
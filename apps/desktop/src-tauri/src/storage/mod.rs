@@ -1,30 +1,191 @@
 use std::{
-    collections::VecDeque,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     fs,
+    io::{self, Write},
     path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
 };
 
 use base64::{engine::general_purpose::STANDARD, Engine};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use image::{
-    codecs::png::{CompressionType, FilterType, PngEncoder},
-    ColorType, ImageEncoder, RgbaImage,
+    codecs::{
+        jpeg::JpegEncoder,
+        png::{CompressionType, FilterType, PngEncoder},
+        webp::WebPEncoder,
+    },
+    imageops, ColorType, GenericImageView, GrayImage, ImageEncoder, ImageFormat, RgbaImage,
 };
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tauri::{AppHandle, Manager};
 use uuid::Uuid;
+use zip::{result::ZipError, write::SimpleFileOptions, ZipArchive, ZipWriter};
 
 use crate::{
     error::{AppError, AppResult},
-    models::{Child, ChildType, Project, ProjectRecord},
+    models::{
+        Atlas, AtlasExport, AtlasFrame, AtlasMeta, AtlasRect, AtlasSize, Child, ChildDefaults,
+        ChildMode, ChildSummary, ChildType, ColorMapping, DiskUsage, ErroredProject, FrameOrder,
+        GridMismatch, GridWarning, ImageDiff, ImageTransform, OutputFormat, PaletteSwatch, Project,
+        ProjectRecord, ProjectRecordScan, ProjectSummary, PruneReport, Rect, ReferenceCompression,
+        RepackedFrame, ScaleFilter, SelfCheckReport, SlicedFrame, SlicedSheet,
+        DEFAULT_CHROMAKEY_COLOR,
+    },
 };
 
 const SUPPORTED_MIMES: [&str; 4] = ["image/png", "image/jpeg", "image/jpg", "image/webp"];
+/// Input formats the API won't take directly but that decode fine locally.
+const TRANSCODED_MIMES: [(&str, ImageFormat); 5] = [
+    ("image/gif", ImageFormat::Gif),
+    ("image/bmp", ImageFormat::Bmp),
+    ("image/x-ms-bmp", ImageFormat::Bmp),
+    ("image/tiff", ImageFormat::Tiff),
+    ("image/tif", ImageFormat::Tiff),
+];
+const ASPECT_MISMATCH_TOLERANCE: f64 = 0.05;
 
 pub struct ParsedDataUrl {
     pub bytes: Vec<u8>,
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct OutputImageOptions {
+    /// Resize so the long edge matches before any other processing.
+    pub target_long_edge: Option<u32>,
+    pub apply_chromakey: bool,
+    pub chromakey_color: Option<[u8; 3]>,
+    pub chromakey_settings: Option<ChromakeySettings>,
+    /// Reduce opaque pixels to at most this many colors after keying.
+    pub palette_colors: Option<u32>,
+    pub sprite_grid: Option<(u32, u32)>,
+    pub edit_mask: Option<EditMask>,
+    pub preserved_regions: Option<PreservedRegions>,
+    pub pad_to_aspect: Option<f64>,
+    /// Score how seamlessly the final image wraps; see [`seamless_score`].
+    pub measure_seams: bool,
+    /// Compare the frames drawn on the keyed sheet with `sprite_grid`; see
+    /// [`detect_sprite_grid`].
+    pub verify_grid: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SavedOutputImage {
+    pub image_path: String,
+    pub raw_image_path: Option<String>,
+    pub grid_warning: Option<GridWarning>,
+    pub seamless_score: Option<f64>,
+    pub grid_mismatch: Option<GridMismatch>,
+}
+
+/// Tuning for chromakey removal. Tolerances are squared RGB distances from the key color.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChromakeySettings {
+    pub seed_tolerance: u32,
+    pub expand_tolerance: u32,
+    pub global_strong_tolerance: u32,
+    pub fringe_passes: usize,
+    /// How strongly (0.0-1.0, 0 = off) key color spill is neutralized on remaining pixels.
+    pub despill_strength: f32,
+    /// Take the key color from the image border; keying is skipped when the border has no
+    /// clear background. White and checkerboard borders are keyed as those backgrounds.
+    pub auto_detect_color: bool,
+    /// Softens the hard keyed edge over this many pixels (0 = off). Leave off for pixel art.
+    pub feather_radius: u32,
+    /// Also flood through diagonal neighbors. This clears thin diagonal strands of
+    /// background, but lets the fill leak through single-pixel diagonal gaps in an outline
+    /// and eat into the subject's interior, so it is off by default.
+    pub eight_connected: bool,
+    pub mode: ChromakeyMode,
+    /// After keying, clear stray pixels fainter than this alpha (0 = off); see
+    /// [`despeckle_alpha`].
+    pub despeckle_min_alpha: u8,
+    /// Faint clusters with at least this many pixels are kept as intentional detail.
+    pub despeckle_min_cluster: usize,
+    pub background: KeyBackground,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChromakeyMode {
+    /// Flood the background, then also clear strong key color anywhere and its fringe.
+    #[default]
+    Aggressive,
+    /// Only clear the background flood-filled in from the borders, so key-colored details
+    /// inside the subject (a green potion, a leaf) survive.
+    BorderOnly,
+}
+
+/// What kind of background is flood-filled away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KeyBackground {
+    /// A saturated key color, such as the default green.
+    #[default]
+    Color,
+    /// Near-white: bright and nearly colorless.
+    White,
+    /// A transparency checkerboard of two alternating grays, detected from the border.
+    /// Keying is skipped when the border doesn't show one.
+    Checkerboard,
+}
+
+impl Default for ChromakeySettings {
+    fn default() -> Self {
+        Self {
+            seed_tolerance: 30_000,
+            expand_tolerance: 45_000,
+            global_strong_tolerance: 36_000,
+            fringe_passes: 2,
+            despill_strength: 0.0,
+            auto_detect_color: false,
+            feather_radius: 0,
+            eight_connected: false,
+            mode: ChromakeyMode::Aggressive,
+            despeckle_min_alpha: 0,
+            despeckle_min_cluster: 4,
+            background: KeyBackground::Color,
+        }
+    }
+}
+
+/// Regions of an existing image that are copied back verbatim after all other processing,
+/// so they come out byte-identical no matter what the model did to them.
+#[derive(Debug, Clone)]
+pub struct PreservedRegions {
+    base: RgbaImage,
+    regions: Vec<Rect>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FrameGrid {
+    pub rows: u32,
+    pub cols: u32,
+    pub order: FrameOrder,
+}
+
+/// Which variant of a child's image a file holds. Every file in a project's images dir is
+/// named through [`image_file_name`] so ownership can be derived from the name alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageKind {
+    /// Final output shown in the UI (chromakeyed when applicable).
+    Output,
+    /// Decoded model output (after any mask compositing) before chromakey is applied.
+    Raw,
+    /// Small preview of an output image, written alongside it.
+    Thumbnail,
+    /// One cell cut out of a sprite sheet, numbered in play order.
+    Frame(usize),
+}
+
+/// The original base image plus a per-pixel edit weight (255 = take the model output,
+/// 0 = keep the base pixel untouched).
+#[derive(Debug, Clone)]
+pub struct EditMask {
+    base: RgbaImage,
+    weights: GrayImage,
+}
+
 pub fn ensure_projects_root(app: &AppHandle) -> AppResult<PathBuf> {
     let root = app
         .path()
@@ -36,6 +197,53 @@ pub fn ensure_projects_root(app: &AppHandle) -> AppResult<PathBuf> {
     Ok(root)
 }
 
+/// Walks the whole data tree, recreating any missing directories. Anything that can't be
+/// repaired automatically is reported as a problem instead.
+pub fn self_check(app: &AppHandle) -> AppResult<SelfCheckReport> {
+    let mut report = SelfCheckReport::default();
+
+    let root = app
+        .path()
+        .app_data_dir()
+        .map_err(|error| AppError::msg(format!("failed to resolve app data dir: {error}")))?
+        .join("projects");
+    if !root.exists() {
+        report
+            .repairs
+            .push(format!("created projects dir {}", root.display()));
+    }
+    let root = ensure_projects_root(app)?;
+
+    for entry in fs::read_dir(&root)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let project_path = entry.path();
+        let dir_name = entry.file_name().to_string_lossy().to_string();
+        if !project_path.join("project.json").exists() {
+            report
+                .problems
+                .push(format!("{dir_name}: missing project.json"));
+            continue;
+        }
+
+        report.projects_checked += 1;
+        for subdir in ["children", "images"] {
+            let path = project_path.join(subdir);
+            if !path.is_dir() {
+                fs::create_dir_all(&path)?;
+                report
+                    .repairs
+                    .push(format!("{dir_name}: recreated missing {subdir}/ dir"));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
 pub fn create_project_record(app: &AppHandle, name: Option<String>) -> AppResult<ProjectRecord> {
     let now = Utc::now();
     let id = Uuid::new_v4().to_string();
@@ -45,6 +253,9 @@ pub fn create_project_record(app: &AppHandle, name: Option<String>) -> AppResult
         created_at: now,
         updated_at: now,
         child_ids: Vec::new(),
+        thumbnail_path: None,
+        defaults: None,
+        variables: BTreeMap::new(),
     };
 
     ensure_project_dirs(app, &id)?;
@@ -53,33 +264,129 @@ pub fn create_project_record(app: &AppHandle, name: Option<String>) -> AppResult
     Ok(record)
 }
 
-pub fn list_project_records(app: &AppHandle) -> AppResult<Vec<ProjectRecord>> {
+/// Lists project summaries newest first, skipping `offset` and returning at most `limit`.
+/// Reads the projects index; if it's missing, unreadable, or doesn't match the project
+/// directories on disk, every project.json is rescanned and the index rebuilt. Unreadable
+/// records are skipped and reported so one bad file doesn't hide the rest.
+pub fn list_project_records(
+    app: &AppHandle,
+    offset: usize,
+    limit: Option<usize>,
+) -> AppResult<ProjectRecordScan> {
     let root = ensure_projects_root(app)?;
-    let mut records = Vec::new();
+    let _guard = lock_project_index();
+    let disk_ids = project_dir_ids(&root)?;
+    let index = match read_json::<ProjectIndex>(&root.join(PROJECT_INDEX_FILE)) {
+        Ok(index) if index.ids() == disk_ids => index,
+        _ => rebuild_project_index(&root, &disk_ids)?,
+    };
 
-    for entry in fs::read_dir(&root)? {
+    Ok(ProjectRecordScan {
+        total: index.projects.len() + index.errored.len(),
+        projects: index
+            .projects
+            .into_iter()
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .collect(),
+        errored: index.errored,
+    })
+}
+
+/// Summaries of every project, kept at the projects root so listing is a single read.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProjectIndex {
+    /// Sorted by `updated_at`, newest first.
+    projects: Vec<ProjectSummary>,
+    errored: Vec<ErroredProject>,
+}
+
+impl ProjectIndex {
+    fn ids(&self) -> HashSet<String> {
+        self.projects
+            .iter()
+            .map(|summary| summary.id.clone())
+            .chain(self.errored.iter().map(|errored| errored.id.clone()))
+            .collect()
+    }
+
+    fn sort(&mut self) {
+        self.projects
+            .sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    }
+}
+
+const PROJECT_INDEX_FILE: &str = "index.json";
+
+/// Serializes read-modify-write cycles on the index across concurrent commands.
+static PROJECT_INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+fn lock_project_index() -> MutexGuard<'static, ()> {
+    PROJECT_INDEX_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Held from picking a child's name until the child is appended, so concurrent generations
+/// in one project neither reuse a name nor drop each other's child ids.
+static CHILD_APPEND_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn lock_child_appends() -> MutexGuard<'static, ()> {
+    CHILD_APPEND_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn project_dir_ids(root: &Path) -> AppResult<HashSet<String>> {
+    let mut ids = HashSet::new();
+    for entry in fs::read_dir(root)? {
         let entry = entry?;
-        if !entry.file_type()?.is_dir() {
-            continue;
+        if entry.file_type()?.is_dir() && entry.path().join("project.json").exists() {
+            ids.insert(entry.file_name().to_string_lossy().to_string());
         }
+    }
+    Ok(ids)
+}
 
-        let project_file = entry.path().join("project.json");
-        if !project_file.exists() {
-            continue;
+fn rebuild_project_index(root: &Path, ids: &HashSet<String>) -> AppResult<ProjectIndex> {
+    let mut index = ProjectIndex::default();
+    for id in ids {
+        match read_json::<ProjectRecord>(&root.join(id).join("project.json")) {
+            Ok(record) => index.projects.push(record.to_summary()),
+            Err(error) => {
+                tracing::warn!("skipping unreadable project {id}: {error}");
+                index.errored.push(ErroredProject {
+                    id: id.clone(),
+                    error: error.to_string(),
+                });
+            }
         }
-
-        let record: ProjectRecord = read_json(&project_file)?;
-        records.push(record);
     }
+    index.sort();
+    write_json(&root.join(PROJECT_INDEX_FILE), &index)?;
+    Ok(index)
+}
 
-    records.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
-    Ok(records)
+/// Applies `update` to the index if one exists. A missing or unreadable index is left for
+/// the next listing to rebuild from disk.
+fn update_project_index(app: &AppHandle, update: impl FnOnce(&mut ProjectIndex)) -> AppResult<()> {
+    let path = ensure_projects_root(app)?.join(PROJECT_INDEX_FILE);
+    let _guard = lock_project_index();
+    let Ok(mut index) = read_json::<ProjectIndex>(&path) else {
+        return Ok(());
+    };
+    update(&mut index);
+    index.sort();
+    write_json(&path, &index)
 }
 
 pub fn load_project_record(app: &AppHandle, project_id: &str) -> AppResult<ProjectRecord> {
     let path = project_file_path(app, project_id)?;
     if !path.exists() {
-        return Err(AppError::msg(format!("project not found: {project_id}")));
+        return Err(AppError::invalid(format!(
+            "project not found: {project_id}"
+        )));
     }
 
     read_json(&path)
@@ -88,7 +395,20 @@ pub fn load_project_record(app: &AppHandle, project_id: &str) -> AppResult<Proje
 pub fn save_project_record(app: &AppHandle, record: &ProjectRecord) -> AppResult<()> {
     ensure_project_dirs(app, &record.id)?;
     let path = project_file_path(app, &record.id)?;
-    write_json(&path, record)
+    write_json(&path, record)?;
+
+    let summary = record.to_summary();
+    update_project_index(app, |index| {
+        index.errored.retain(|errored| errored.id != summary.id);
+        match index
+            .projects
+            .iter_mut()
+            .find(|existing| existing.id == summary.id)
+        {
+            Some(existing) => *existing = summary,
+            None => index.projects.push(summary),
+        }
+    })
 }
 
 pub fn update_project_name(
@@ -109,15 +429,424 @@ pub fn update_project_name(
     Ok(record)
 }
 
+pub fn set_project_defaults(
+    app: &AppHandle,
+    project_id: &str,
+    defaults: ChildDefaults,
+) -> AppResult<ProjectRecord> {
+    let mut record = load_project_record(app, project_id)?;
+    record.defaults = Some(defaults);
+    record.updated_at = Utc::now();
+    save_project_record(app, &record)?;
+    Ok(record)
+}
+
+pub fn set_project_variables(
+    app: &AppHandle,
+    project_id: &str,
+    variables: BTreeMap<String, String>,
+) -> AppResult<ProjectRecord> {
+    let mut record = load_project_record(app, project_id)?;
+    record.variables = variables;
+    record.updated_at = Utc::now();
+    save_project_record(app, &record)?;
+    Ok(record)
+}
+
 pub fn delete_project(app: &AppHandle, project_id: &str) -> AppResult<()> {
     let project_dir = project_dir(app, project_id)?;
     if project_dir.exists() {
         fs::remove_dir_all(project_dir)?;
     }
 
+    update_project_index(app, |index| {
+        index.projects.retain(|summary| summary.id != project_id);
+        index.errored.retain(|errored| errored.id != project_id);
+    })
+}
+
+/// Copies a project under a fresh id. Every child gets a new id, and `base_child_id` links
+/// and image paths are rewritten so the edit lineage stays intact inside the copy. Project
+/// metadata (defaults and variables) is copied as is.
+pub fn duplicate_project(
+    app: &AppHandle,
+    project_id: &str,
+    new_name: Option<String>,
+) -> AppResult<ProjectRecord> {
+    let source = load_project_record(app, project_id)?;
+    let name = new_name
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| format!("{} copy", source.name));
+    let mut copy = create_project_record(app, Some(name))?;
+
+    let id_map = source
+        .child_ids
+        .iter()
+        .map(|id| (id.clone(), Uuid::new_v4().to_string()))
+        .collect::<HashMap<_, _>>();
+    let source_images = images_dir(app, project_id)?;
+    let copy_images = images_dir(app, &copy.id)?;
+    let renamed_path = |path: &Path| -> Option<PathBuf> {
+        if path.parent() != Some(source_images.as_path()) {
+            return None;
+        }
+        let file_name = path.file_name()?.to_str()?;
+        let (child_id, rest) = file_name.split_once('_')?;
+        let new_id = id_map.get(child_id)?;
+        Some(copy_images.join(format!("{new_id}_{rest}")))
+    };
+    let rewrite = |path: &String| {
+        renamed_path(Path::new(path))
+            .map(|renamed| renamed.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.clone())
+    };
+
+    for entry in fs::read_dir(&source_images)? {
+        let path = entry?.path();
+        if let Some(dest) = renamed_path(&path) {
+            fs::copy(&path, dest)?;
+        }
+    }
+
+    for old_id in &source.child_ids {
+        let mut child = load_child(app, project_id, old_id)?;
+        child.id = id_map[old_id].clone();
+        child.project_id = copy.id.clone();
+        if let Some(base_id) = child.inputs.base_child_id.as_mut() {
+            if let Some(new_id) = id_map.get(base_id) {
+                *base_id = new_id.clone();
+            }
+        }
+        child.inputs.base_image_path = child.inputs.base_image_path.as_ref().map(rewrite);
+        child.outputs.image_paths = child.outputs.image_paths.iter().map(rewrite).collect();
+        child.outputs.raw_image_paths = child.outputs.raw_image_paths.iter().map(rewrite).collect();
+        child.outputs.primary_image_path = child.outputs.primary_image_path.as_ref().map(rewrite);
+        save_child(app, &child)?;
+        copy.child_ids.push(child.id);
+    }
+
+    copy.thumbnail_path = source.thumbnail_path.as_ref().map(rewrite);
+    copy.defaults = source.defaults.clone();
+    copy.variables = source.variables.clone();
+    save_project_record(app, &copy)?;
+    Ok(copy)
+}
+
+/// Zips `project.json`, `children/` and `images/` into a single portable archive.
+pub fn export_project_archive(app: &AppHandle, project_id: &str, dest: &Path) -> AppResult<()> {
+    load_project_record(app, project_id)?;
+    let project_dir = project_dir(app, project_id)?;
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut archive = ZipWriter::new(fs::File::create(dest)?);
+
+    archive.start_file("project.json", options)?;
+    io::copy(
+        &mut fs::File::open(project_dir.join("project.json"))?,
+        &mut archive,
+    )?;
+    for subdir in ["children", "images"] {
+        for entry in fs::read_dir(project_dir.join(subdir))? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            archive.start_file(format!("{subdir}/{name}"), options)?;
+            io::copy(&mut fs::File::open(entry.path())?, &mut archive)?;
+        }
+    }
+
+    archive.finish()?;
     Ok(())
 }
 
+/// Unpacks an archive written by [`export_project_archive`] under a fresh project id, so an
+/// import never overwrites an existing project. Child records are pointed at the new
+/// project and their image paths at the new images dir. A failed import removes whatever it
+/// had unpacked, so no half-written project is left behind.
+pub fn import_project_archive(app: &AppHandle, archive_path: &Path) -> AppResult<ProjectRecord> {
+    let mut archive = ZipArchive::new(fs::File::open(archive_path)?)?;
+    let record: ProjectRecord = match archive.by_name("project.json") {
+        Ok(file) => serde_json::from_reader(file)?,
+        Err(ZipError::FileNotFound) => {
+            return Err(AppError::invalid(format!(
+                "{} is not a project archive: missing project.json",
+                archive_path.display()
+            )))
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    let new_id = Uuid::new_v4().to_string();
+    unpack_project_archive(app, &mut archive, record, &new_id).inspect_err(|_| {
+        let result = project_dir(app, &new_id).and_then(|dir| {
+            if dir.exists() {
+                fs::remove_dir_all(dir)?;
+            }
+            Ok(())
+        });
+        if let Err(error) = result {
+            tracing::warn!("failed to remove partly imported project {new_id}: {error}");
+        }
+    })
+}
+
+fn unpack_project_archive(
+    app: &AppHandle,
+    archive: &mut ZipArchive<fs::File>,
+    mut record: ProjectRecord,
+    new_id: &str,
+) -> AppResult<ProjectRecord> {
+    ensure_project_dirs(app, new_id)?;
+    let project_dir = project_dir(app, new_id)?;
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let Some(relative) = file.enclosed_name() else {
+            continue;
+        };
+        let in_known_dir = relative
+            .parent()
+            .is_some_and(|parent| parent == Path::new("children") || parent == Path::new("images"));
+        if !file.is_file() || !in_known_dir {
+            continue;
+        }
+        io::copy(
+            &mut file,
+            &mut fs::File::create(project_dir.join(relative))?,
+        )?;
+    }
+
+    let images_dir = images_dir(app, new_id)?;
+    let relocate = |path: &String| {
+        // Archives can come from another OS, so split on either separator.
+        let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+        let local = images_dir.join(file_name);
+        if local.exists() {
+            local.to_string_lossy().to_string()
+        } else {
+            path.clone()
+        }
+    };
+    let mut child_ids = Vec::new();
+    for child_id in &record.child_ids {
+        let Ok(mut child) = load_child(app, new_id, child_id) else {
+            continue;
+        };
+        child.project_id = new_id.to_string();
+        child.inputs.base_image_path = child.inputs.base_image_path.as_ref().map(relocate);
+        child.outputs.image_paths = child.outputs.image_paths.iter().map(relocate).collect();
+        child.outputs.raw_image_paths =
+            child.outputs.raw_image_paths.iter().map(relocate).collect();
+        child.outputs.primary_image_path = child.outputs.primary_image_path.as_ref().map(relocate);
+        save_child(app, &child)?;
+        child_ids.push(child.id);
+    }
+
+    record.id = new_id.to_string();
+    record.child_ids = child_ids;
+    record.thumbnail_path = record.thumbnail_path.as_ref().map(relocate);
+    record.updated_at = Utc::now();
+    save_project_record(app, &record)?;
+    Ok(record)
+}
+
+/// Checks that a project's record and every child and image it references exist and parse.
+/// Returns one line per problem; an empty list means the project is intact.
+pub fn verify_project(app: &AppHandle, project_id: &str) -> AppResult<Vec<String>> {
+    let dir = project_dir(app, project_id)?;
+    if !dir.is_dir() {
+        return Err(AppError::invalid(format!(
+            "project not found: {project_id}"
+        )));
+    }
+
+    let mut problems = Vec::new();
+    let record_path = project_file_path(app, project_id)?;
+    let record = match read_json::<ProjectRecord>(&record_path) {
+        Ok(record) => record,
+        Err(error) => {
+            problems.push(format!("project.json is unreadable: {error}"));
+            return Ok(problems);
+        }
+    };
+    if record.id != project_id {
+        problems.push(format!(
+            "project.json has id {} but lives in {project_id}",
+            record.id
+        ));
+    }
+
+    fn check_file(problems: &mut Vec<String>, owner: &str, path: &str) {
+        if !Path::new(path).is_file() {
+            problems.push(format!("{owner}: missing file {path}"));
+        }
+    }
+    if let Some(thumbnail_path) = &record.thumbnail_path {
+        check_file(&mut problems, "project thumbnail", thumbnail_path);
+    }
+
+    for child_id in &record.child_ids {
+        let child_path = child_file_path(app, project_id, child_id)?;
+        if !child_path.is_file() {
+            check_file(
+                &mut problems,
+                &format!("child {child_id}"),
+                &child_path.to_string_lossy(),
+            );
+            continue;
+        }
+        let child = match read_json::<Child>(&child_path) {
+            Ok(child) => child,
+            Err(error) => {
+                problems.push(format!("child {child_id}: unreadable json: {error}"));
+                continue;
+            }
+        };
+
+        let owner = format!("child {child_id}");
+        let outputs = &child.outputs;
+        for path in outputs.image_paths.iter().chain(&outputs.raw_image_paths) {
+            check_file(&mut problems, &owner, path);
+        }
+        if let Some(primary) = &outputs.primary_image_path {
+            check_file(&mut problems, &owner, primary);
+        }
+        if let Some(base_child_id) = &child.inputs.base_child_id {
+            if !record.child_ids.contains(base_child_id) {
+                problems.push(format!(
+                    "{owner}: base child {base_child_id} is not in the project"
+                ));
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+pub fn project_disk_usage(app: &AppHandle, project_id: &str) -> AppResult<DiskUsage> {
+    let dir = project_dir(app, project_id)?;
+    if !dir.is_dir() {
+        return Err(AppError::invalid(format!(
+            "project not found: {project_id}"
+        )));
+    }
+
+    let (total, _) = dir_size(&dir)?;
+    let (images, image_count) = dir_size(&images_dir(app, project_id)?)?;
+    Ok(DiskUsage {
+        total,
+        images,
+        metadata: total - images,
+        image_count,
+    })
+}
+
+/// `project_disk_usage` summed over every project.
+pub fn all_projects_disk_usage(app: &AppHandle) -> AppResult<DiskUsage> {
+    let root = ensure_projects_root(app)?;
+    let mut usage = DiskUsage::default();
+    for project_id in project_dir_ids(&root)? {
+        let project = project_disk_usage(app, &project_id)?;
+        usage.total += project.total;
+        usage.images += project.images;
+        usage.metadata += project.metadata;
+        usage.image_count += project.image_count;
+    }
+    Ok(usage)
+}
+
+/// Deletes output images no child references any more, along with their raw copies and
+/// thumbnails. Only `{child_id}_{index}.png` files belonging to children that still load are
+/// considered, so anything unexpected in the images dir is left alone.
+pub fn prune_project(app: &AppHandle, project_id: &str, dry_run: bool) -> AppResult<PruneReport> {
+    let record = load_project_record(app, project_id)?;
+    let images = images_dir(app, project_id)?;
+
+    let mut known_children = HashSet::new();
+    let mut referenced = HashSet::new();
+    for child_id in &record.child_ids {
+        let Ok(child) = load_child(app, project_id, child_id) else {
+            continue;
+        };
+        let outputs = &child.outputs;
+        referenced.extend(
+            outputs
+                .image_paths
+                .iter()
+                .chain(&outputs.raw_image_paths)
+                .chain(&outputs.primary_image_path)
+                .map(PathBuf::from),
+        );
+        known_children.insert(child.id);
+    }
+    referenced.extend(record.thumbnail_path.iter().map(PathBuf::from));
+
+    let mut report = PruneReport::default();
+    if !images.is_dir() {
+        return Ok(report);
+    }
+    for entry in fs::read_dir(&images)? {
+        let path = entry?.path();
+        let Some((child_id, index)) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(parse_output_file_name)
+        else {
+            continue;
+        };
+        if !known_children.contains(child_id) || referenced.contains(&path) {
+            continue;
+        }
+
+        let companions = [ImageKind::Raw, ImageKind::Thumbnail]
+            .map(|kind| images.join(image_file_name(child_id, index, kind)));
+        for candidate in std::iter::once(path.clone()).chain(companions) {
+            if !candidate.is_file() || referenced.contains(&candidate) {
+                continue;
+            }
+            report.freed_bytes += fs::metadata(&candidate)?.len();
+            if !dry_run {
+                fs::remove_file(&candidate)?;
+            }
+            report.files.push(candidate.to_string_lossy().to_string());
+        }
+    }
+
+    Ok(report)
+}
+
+/// Splits `{child_id}_{index}.png` into its parts; other image names yield `None`.
+fn parse_output_file_name(name: &str) -> Option<(&str, usize)> {
+    let (child_id, index) = name.strip_suffix(".png")?.rsplit_once('_')?;
+    if child_id.is_empty() || child_id.contains('_') {
+        return None;
+    }
+    Some((child_id, index.parse().ok()?))
+}
+
+/// Total bytes and file count under `dir`, recursively. A missing dir counts as empty.
+fn dir_size(dir: &Path) -> AppResult<(u64, usize)> {
+    if !dir.is_dir() {
+        return Ok((0, 0));
+    }
+
+    let mut bytes = 0;
+    let mut files = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            let (sub_bytes, sub_files) = dir_size(&entry.path())?;
+            bytes += sub_bytes;
+            files += sub_files;
+        } else {
+            bytes += metadata.len();
+            files += 1;
+        }
+    }
+    Ok((bytes, files))
+}
+
 pub fn load_project(app: &AppHandle, project_id: &str) -> AppResult<Project> {
     let record = load_project_record(app, project_id)?;
     let children = record
@@ -135,157 +864,1973 @@ pub fn load_project(app: &AppHandle, project_id: &str) -> AppResult<Project> {
     })
 }
 
-pub fn append_child(app: &AppHandle, project_id: &str, child: &Child) -> AppResult<()> {
-    save_child(app, child)?;
-
-    let mut record = load_project_record(app, project_id)?;
-    record.child_ids.push(child.id.clone());
-    record.updated_at = Utc::now();
-    save_project_record(app, &record)
+/// Summaries in record order, read without building the rest of each child. Like
+/// [`load_project`], children whose JSON can't be read are left out.
+pub fn list_child_summaries(app: &AppHandle, project_id: &str) -> AppResult<Vec<ChildSummary>> {
+    let record = load_project_record(app, project_id)?;
+    Ok(record
+        .child_ids
+        .iter()
+        .filter_map(|child_id| load_child_summary(app, project_id, child_id).ok())
+        .collect())
 }
 
-pub fn save_child(app: &AppHandle, child: &Child) -> AppResult<()> {
-    let child_path = child_file_path(app, &child.project_id, &child.id)?;
-    write_json(&child_path, child)
+/// Serde skips every field not named here, so the payload and inputs are scanned past rather
+/// than built into values.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredChildSummary {
+    id: String,
+    name: String,
+    r#type: ChildType,
+    mode: ChildMode,
+    created_at: DateTime<Utc>,
+    outputs: StoredSummaryOutputs,
 }
 
-pub fn load_child(app: &AppHandle, project_id: &str, child_id: &str) -> AppResult<Child> {
-    let child_path = child_file_path(app, project_id, child_id)?;
-    if !child_path.exists() {
-        return Err(AppError::msg(format!(
-            "child {child_id} not found in project {project_id}"
-        )));
-    }
-    read_json(&child_path)
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredSummaryOutputs {
+    primary_image_path: Option<String>,
 }
 
-pub fn next_child_name(
+fn load_child_summary(
     app: &AppHandle,
     project_id: &str,
-    child_type: ChildType,
-) -> AppResult<String> {
-    let project = load_project(app, project_id)?;
-    let count = project
-        .children
-        .iter()
-        .filter(|child| child.r#type == child_type)
-        .count()
-        + 1;
+    child_id: &str,
+) -> AppResult<ChildSummary> {
+    let stored: StoredChildSummary = read_json(&child_file_path(app, project_id, child_id)?)?;
+    Ok(ChildSummary {
+        id: stored.id,
+        name: stored.name,
+        r#type: stored.r#type,
+        mode: stored.mode,
+        created_at: stored.created_at,
+        primary_image_path: stored.outputs.primary_image_path,
+    })
+}
 
-    let prefix = match child_type {
-        ChildType::Generate => "gen",
-        ChildType::Edit => "edit",
-    };
+/// The record is saved last, so the child only becomes visible once its JSON and images are
+/// on disk. If any step fails, the child's files are removed and the project is left as it was.
+pub fn append_child(app: &AppHandle, project_id: &str, child: &Child) -> AppResult<()> {
+    let result = load_project_record(app, project_id).and_then(|mut record| {
+        save_child(app, child)?;
+        record.child_ids.push(child.id.clone());
+        record.thumbnail_path = child_thumbnail_path(child).or(record.thumbnail_path);
+        record.updated_at = Utc::now();
+        save_project_record(app, &record)
+    });
+    if result.is_err() {
+        discard_child_files(app, project_id, &child.id);
+    }
+    result
+}
 
-    Ok(format!("{prefix}-{count:04}"))
+/// Best-effort rollback for a child that never made it into the project record: removes its
+/// JSON and every image named after it. Failures are logged, since the caller is already
+/// reporting the error that got it here.
+pub fn discard_child_files(app: &AppHandle, project_id: &str, child_id: &str) {
+    let result = images_dir(app, project_id).and_then(|dir| remove_owned_images(&dir, child_id));
+    if let Err(error) = result {
+        tracing::warn!("failed to remove images for discarded child {child_id}: {error}");
+    }
+    let child_path = match child_file_path(app, project_id, child_id) {
+        Ok(path) => path,
+        Err(error) => {
+            tracing::warn!("failed to resolve discarded child {child_id}: {error}");
+            return;
+        }
+    };
+    if child_path.exists() {
+        if let Err(error) = fs::remove_file(&child_path) {
+            tracing::warn!("failed to remove discarded child {child_id}: {error}");
+        }
+    }
 }
 
-pub fn write_output_image(
+/// Replaces the record's child order with `ordered_ids`, which must name every current child
+/// exactly once. Holds the append lock so a child added mid-reorder can't be dropped.
+pub fn reorder_children(
     app: &AppHandle,
     project_id: &str,
-    child_id: &str,
-    index: usize,
+    ordered_ids: Vec<String>,
+) -> AppResult<Vec<String>> {
+    let _append_guard = lock_child_appends();
+    let mut record = load_project_record(app, project_id)?;
+
+    let mut seen = HashSet::new();
+    if let Some(duplicate) = ordered_ids.iter().find(|id| !seen.insert(id.as_str())) {
+        return Err(AppError::invalid(format!(
+            "child {duplicate} is listed more than once"
+        )));
+    }
+    if let Some(unknown) = ordered_ids.iter().find(|id| !record.child_ids.contains(id)) {
+        return Err(AppError::invalid(format!(
+            "child {unknown} is not in project {project_id}"
+        )));
+    }
+    if let Some(missing) = record
+        .child_ids
+        .iter()
+        .find(|id| !seen.contains(id.as_str()))
+    {
+        return Err(AppError::invalid(format!(
+            "child {missing} is missing from the new order"
+        )));
+    }
+
+    record.child_ids = ordered_ids;
+    record.updated_at = Utc::now();
+    save_project_record(app, &record)?;
+    Ok(record.child_ids)
+}
+
+/// Removes the child's JSON, every image it owns, and its entry in the project record.
+/// Returns the ids of remaining children whose `base_child_id` pointed at it.
+pub fn delete_child(app: &AppHandle, project_id: &str, child_id: &str) -> AppResult<Vec<String>> {
+    let mut record = load_project_record(app, project_id)?;
+    let child = load_child(app, project_id, child_id)?;
+
+    let images_dir = images_dir(app, project_id)?;
+    for path in child
+        .outputs
+        .image_paths
+        .iter()
+        .chain(&child.outputs.raw_image_paths)
+    {
+        let path = Path::new(path);
+        if path.parent() == Some(images_dir.as_path()) && path.exists() {
+            fs::remove_file(path)?;
+        }
+    }
+    remove_owned_images(&images_dir, child_id)?;
+    fs::remove_file(child_file_path(app, project_id, child_id)?)?;
+
+    record.child_ids.retain(|id| id != child_id);
+    let thumbnail_deleted = record
+        .thumbnail_path
+        .as_deref()
+        .is_some_and(|path| !Path::new(path).exists());
+    if thumbnail_deleted {
+        record.thumbnail_path = record
+            .child_ids
+            .iter()
+            .rev()
+            .filter_map(|id| load_child(app, project_id, id).ok())
+            .find_map(|child| child_thumbnail_path(&child));
+    }
+    record.updated_at = Utc::now();
+    save_project_record(app, &record)?;
+
+    let orphaned = record
+        .child_ids
+        .iter()
+        .filter_map(|id| load_child(app, project_id, id).ok())
+        .filter(|other| other.inputs.base_child_id.as_deref() == Some(child_id))
+        .map(|other| other.id)
+        .collect();
+    Ok(orphaned)
+}
+
+pub fn update_child_annotations(
+    app: &AppHandle,
+    project_id: &str,
+    child_id: &str,
+    notes: Option<String>,
+    label_color: Option<String>,
+) -> AppResult<Child> {
+    let mut child = load_child(app, project_id, child_id)?;
+    child.notes = notes.filter(|value| !value.trim().is_empty());
+    child.label_color = label_color.filter(|value| !value.trim().is_empty());
+    save_child(app, &child)?;
+    touch_project(app, project_id)?;
+
+    Ok(child)
+}
+
+pub fn touch_project(app: &AppHandle, project_id: &str) -> AppResult<ProjectRecord> {
+    let mut record = load_project_record(app, project_id)?;
+    record.updated_at = Utc::now();
+    save_project_record(app, &record)?;
+    Ok(record)
+}
+
+/// Bumps `updated_at` after `child` changed, re-pointing the project thumbnail at it when it
+/// is the newest child (the one the thumbnail shows).
+pub fn touch_project_for_child(app: &AppHandle, child: &Child) -> AppResult<ProjectRecord> {
+    let mut record = load_project_record(app, &child.project_id)?;
+    if record.child_ids.last() == Some(&child.id) {
+        record.thumbnail_path = child_thumbnail_path(child).or(record.thumbnail_path);
+    }
+    record.updated_at = Utc::now();
+    save_project_record(app, &record)?;
+    Ok(record)
+}
+
+/// Frames and thumbnails aren't listed on the child, but their names carry its id.
+fn remove_owned_images(images_dir: &Path, child_id: &str) -> AppResult<()> {
+    let owned_prefix = format!("{child_id}_");
+    for entry in fs::read_dir(images_dir)? {
+        let entry = entry?;
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(&owned_prefix)
+        {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+pub fn save_child(app: &AppHandle, child: &Child) -> AppResult<()> {
+    let child_path = child_file_path(app, &child.project_id, &child.id)?;
+    write_json(&child_path, child)
+}
+
+pub fn load_child(app: &AppHandle, project_id: &str, child_id: &str) -> AppResult<Child> {
+    let child_path = child_file_path(app, project_id, child_id)?;
+    if !child_path.exists() {
+        return Err(AppError::invalid(format!(
+            "child {child_id} not found in project {project_id}"
+        )));
+    }
+    read_json(&child_path)
+}
+
+/// Next `gen-0001` / `edit-0001` style name: one past the highest existing suffix for the
+/// type, so deleting or renaming children can't produce a duplicate. Callers hold
+/// `lock_child_appends` from here until the child is appended.
+pub fn next_child_name(
+    app: &AppHandle,
+    project_id: &str,
+    child_type: ChildType,
+) -> AppResult<String> {
+    let prefix = match child_type {
+        ChildType::Generate => "gen",
+        ChildType::Edit => "edit",
+    };
+
+    let project = load_project(app, project_id)?;
+    let next = project
+        .children
+        .iter()
+        .filter_map(|child| {
+            child
+                .name
+                .strip_prefix(prefix)?
+                .strip_prefix('-')?
+                .parse::<u32>()
+                .ok()
+        })
+        .max()
+        .unwrap_or(0)
+        + 1;
+
+    Ok(format!("{prefix}-{next:04}"))
+}
+
+pub fn write_output_image(
+    app: &AppHandle,
+    project_id: &str,
+    child_id: &str,
+    index: usize,
     data_url: &str,
-    apply_chromakey: bool,
+    options: &OutputImageOptions,
+) -> AppResult<SavedOutputImage> {
+    let image_bytes = parse_data_url(data_url)?;
+    let mut image = image::load_from_memory(&image_bytes.bytes)?.into_rgba8();
+    if let Some(edit_mask) = &options.edit_mask {
+        image = composite_with_edit_mask(&image, edit_mask);
+    }
+
+    let raw_image_path = if options.apply_chromakey {
+        let raw_path =
+            images_dir(app, project_id)?.join(image_file_name(child_id, index, ImageKind::Raw));
+        let png_bytes = encode_png_optimized(image.as_raw(), image.width(), image.height())?;
+        fs::write(&raw_path, png_bytes)?;
+        Some(raw_path.to_string_lossy().to_string())
+    } else {
+        None
+    };
+
+    let image = process_output_image(image, options);
+    let grid_warning = options
+        .sprite_grid
+        .and_then(|(rows, cols)| validate_sprite_grid(image.width(), image.height(), rows, cols));
+    let seamless_score = options.measure_seams.then(|| seamless_score(&image));
+    let grid_mismatch = options
+        .sprite_grid
+        .filter(|_| options.verify_grid)
+        .and_then(|(rows, cols)| verify_sprite_grid(&image, rows, cols));
+    if let Some(mismatch) = &grid_mismatch {
+        tracing::warn!(
+            child_id,
+            index,
+            requested = %format!("{}x{}", mismatch.requested_rows, mismatch.requested_cols),
+            detected = %format!("{}x{}", mismatch.detected_rows, mismatch.detected_cols),
+            "sprite sheet doesn't match the requested grid"
+        );
+    }
+    Ok(SavedOutputImage {
+        image_path: save_output_image(app, project_id, child_id, index, &image)?,
+        raw_image_path,
+        grid_warning,
+        seamless_score,
+        grid_mismatch,
+    })
+}
+
+/// How seamlessly `image` tiles, from 0 to 1. The color jump across each wrap seam (right
+/// edge into left, bottom into top) is compared with the average jump between neighboring
+/// pixels inside the image, so a busy texture isn't marked down for being busy. 1.0 means
+/// the seams are no more abrupt than the texture itself; low scores mean the model drew a
+/// border or an edge that doesn't continue.
+pub fn seamless_score(image: &RgbaImage) -> f64 {
+    let (width, height) = image.dimensions();
+    if width < 2 || height < 2 {
+        return 1.0;
+    }
+
+    let delta = |a: (u32, u32), b: (u32, u32)| -> u64 {
+        let (a, b) = (image.get_pixel(a.0, a.1), image.get_pixel(b.0, b.1));
+        (0..3)
+            .map(|channel| a[channel].abs_diff(b[channel]) as u64)
+            .sum()
+    };
+    let (mut seam, mut interior) = (0u64, 0u64);
+    for y in 0..height {
+        seam += delta((width - 1, y), (0, y));
+        for x in 1..width {
+            interior += delta((x - 1, y), (x, y));
+        }
+    }
+    for x in 0..width {
+        seam += delta((x, height - 1), (x, 0));
+        for y in 1..height {
+            interior += delta((x, y - 1), (x, y));
+        }
+    }
+
+    let seam_mean = seam as f64 / (width + height) as f64;
+    let interior_count = (width - 1) as u64 * height as u64 + (height - 1) as u64 * width as u64;
+    let interior_mean = interior as f64 / interior_count as f64;
+    // The +1 keeps flat images from dividing by zero or scoring a tiny seam as a failure.
+    ((interior_mean + 1.0) / (seam_mean + 1.0)).min(1.0)
+}
+
+/// Reports when `width`x`height` can't be split into `rows`x`cols` equal cells.
+pub fn validate_sprite_grid(width: u32, height: u32, rows: u32, cols: u32) -> Option<GridWarning> {
+    if rows == 0 || cols == 0 {
+        return None;
+    }
+    let leftover_x = width % cols;
+    let leftover_y = height % rows;
+    if leftover_x == 0 && leftover_y == 0 {
+        return None;
+    }
+
+    Some(GridWarning {
+        width,
+        height,
+        rows,
+        cols,
+        leftover_x,
+        leftover_y,
+    })
+}
+
+/// Share of a row or column that may be opaque while it still counts as a gap, so a stray
+/// speck the keyer missed doesn't join two frames.
+const GRID_GAP_MAX_SHARE: f64 = 0.002;
+/// Bands of content thinner than this share of the axis are specks, not frames.
+const GRID_BAND_MIN_SHARE: f64 = 0.02;
+
+/// Reports when the frames drawn on a keyed sheet don't form a `rows`x`cols` grid. Sheets
+/// whose layout can't be read at all (see [`detect_sprite_grid`]) pass unreported.
+pub fn verify_sprite_grid(image: &RgbaImage, rows: u32, cols: u32) -> Option<GridMismatch> {
+    let (detected_rows, detected_cols) = detect_sprite_grid(image)?;
+    if detected_rows == rows && detected_cols == cols {
+        return None;
+    }
+
+    Some(GridMismatch {
+        requested_rows: rows,
+        requested_cols: cols,
+        detected_rows,
+        detected_cols,
+    })
+}
+
+/// Estimates a keyed sheet's `(rows, cols)` by counting the bands of content separated by
+/// fully transparent rows and columns. Frames that touch or overlap their neighbors read as
+/// one band, and a frame split by a gap (a character and a detached effect) as two, so this
+/// is a hint rather than a verdict. Returns `None` when nothing is transparent, e.g. keying
+/// was skipped, since every axis would then read as a single band.
+pub fn detect_sprite_grid(image: &RgbaImage) -> Option<(u32, u32)> {
+    let (width, height) = image.dimensions();
+    let mut column_counts = vec![0u32; width as usize];
+    let mut row_counts = vec![0u32; height as usize];
+    let mut any_transparent = false;
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel[3] > 0 {
+            column_counts[x as usize] += 1;
+            row_counts[y as usize] += 1;
+        } else {
+            any_transparent = true;
+        }
+    }
+    if !any_transparent {
+        return None;
+    }
+
+    let cols = count_content_bands(&column_counts, height);
+    let rows = count_content_bands(&row_counts, width);
+    (rows > 0 && cols > 0).then_some((rows, cols))
+}
+
+/// Runs of `counts` above the gap threshold, ignoring runs too thin to be a frame. `span` is
+/// how many pixels each count was taken over.
+fn count_content_bands(counts: &[u32], span: u32) -> u32 {
+    let gap_limit = (span as f64 * GRID_GAP_MAX_SHARE) as u32;
+    let min_band = ((counts.len() as f64 * GRID_BAND_MIN_SHARE) as usize).max(1);
+    let mut bands = 0;
+    let mut run = 0;
+    // The trailing 0 closes a band that runs to the edge.
+    for &count in counts.iter().chain(std::iter::once(&0)) {
+        if count > gap_limit {
+            run += 1;
+        } else {
+            if run >= min_band {
+                bands += 1;
+            }
+            run = 0;
+        }
+    }
+    bands
+}
+
+/// Re-runs the output pipeline for an already-saved image, starting from its raw copy when
+/// one exists so repeated reprocessing doesn't compound. Older children without a raw copy
+/// fall back to the current output.
+pub fn reprocess_output_image(
+    app: &AppHandle,
+    project_id: &str,
+    child_id: &str,
+    index: usize,
+    raw_image_path: Option<&str>,
+    options: &OutputImageOptions,
+) -> AppResult<String> {
+    let source_path = match raw_image_path.map(PathBuf::from) {
+        Some(raw_path) if raw_path.exists() => raw_path,
+        _ => images_dir(app, project_id)?.join(image_file_name(child_id, index, ImageKind::Output)),
+    };
+
+    let image = process_output_image(load_rgba_image(&source_path)?, options);
+    save_output_image(app, project_id, child_id, index, &image)
+}
+
+fn process_output_image(mut image: RgbaImage, options: &OutputImageOptions) -> RgbaImage {
+    if let Some(long_edge) = options.target_long_edge {
+        if image.width().max(image.height()) != long_edge {
+            image = resize_to_long_edge(&image, long_edge);
+        }
+    }
+    if options.apply_chromakey {
+        let settings = options.chromakey_settings.unwrap_or_default();
+        let key = match settings.background {
+            KeyBackground::Color if settings.auto_detect_color => {
+                auto_detected_keyer(&image, settings)
+            }
+            KeyBackground::Color => Some(Keyer::new(
+                options.chromakey_color.unwrap_or(DEFAULT_CHROMAKEY_COLOR),
+                settings,
+            )),
+            KeyBackground::White => Some(Keyer::new([255; 3], settings)),
+            KeyBackground::Checkerboard => {
+                detect_checkerboard(&image).map(|levels| Keyer::checkerboard(levels, settings))
+            }
+        };
+        if let Some(key) = key {
+            apply_chromakey_transparency(&mut image, options.sprite_grid, key);
+        }
+    }
+    if let Some(max_colors) = options.palette_colors {
+        quantize_to_palette(&mut image, max_colors);
+    }
+    if let Some(preserved) = &options.preserved_regions {
+        image = restore_preserved_regions(&image, preserved);
+    }
+    if let Some(target) = options.pad_to_aspect {
+        if aspect_mismatches(image.width(), image.height(), target) {
+            image = pad_to_aspect(&image, target);
+        }
+    }
+
+    image
+}
+
+pub fn save_output_image(
+    app: &AppHandle,
+    project_id: &str,
+    child_id: &str,
+    index: usize,
+    image: &RgbaImage,
+) -> AppResult<String> {
+    let image_path =
+        images_dir(app, project_id)?.join(image_file_name(child_id, index, ImageKind::Output));
+
+    let png_bytes = encode_png_optimized(image.as_raw(), image.width(), image.height())?;
+    fs::write(&image_path, png_bytes)?;
+    let thumbnail_path =
+        images_dir(app, project_id)?.join(image_file_name(child_id, index, ImageKind::Thumbnail));
+    write_thumbnail(image, &thumbnail_path, THUMBNAIL_MAX_DIM)?;
+
+    Ok(image_path.to_string_lossy().to_string())
+}
+
+const THUMBNAIL_MAX_DIM: u32 = 256;
+
+/// Writes the thumbnail of the child output at `image_path` next to it, scaled to fit
+/// within `max_dim`.
+pub fn generate_thumbnail(image_path: &Path, max_dim: u32) -> AppResult<String> {
+    let thumbnail_path = thumbnail_path_for(image_path).ok_or_else(|| {
+        AppError::msg(format!(
+            "not a child output image: {}",
+            image_path.display()
+        ))
+    })?;
+    let image = load_rgba_image(image_path)?;
+    write_thumbnail(&image, &thumbnail_path, max_dim)?;
+    Ok(thumbnail_path.to_string_lossy().to_string())
+}
+
+/// The thumbnail path for the child output at `image_path`, named by [`image_file_name`]
+/// like the one `save_output_image` writes. `None` for images that aren't child outputs.
+fn thumbnail_path_for(image_path: &Path) -> Option<PathBuf> {
+    let name = image_path.file_name()?.to_str()?;
+    let (child_id, index) = parse_output_file_name(name)?;
+    Some(image_path.with_file_name(image_file_name(child_id, index, ImageKind::Thumbnail)))
+}
+
+fn write_thumbnail(image: &RgbaImage, dest: &Path, max_dim: u32) -> AppResult<()> {
+    let thumbnail = if image.width().max(image.height()) > max_dim {
+        resize_to_long_edge(image, max_dim)
+    } else {
+        image.clone()
+    };
+    let png_bytes =
+        encode_png_optimized(thumbnail.as_raw(), thumbnail.width(), thumbnail.height())?;
+    fs::write(dest, png_bytes)?;
+    Ok(())
+}
+
+/// The thumbnail of the child's primary image, generated on demand for children saved
+/// before thumbnails existed.
+fn child_thumbnail_path(child: &Child) -> Option<String> {
+    let primary = Path::new(child.outputs.primary_image_path.as_deref()?);
+    let thumbnail_path = thumbnail_path_for(primary)?;
+    if thumbnail_path.exists() {
+        return Some(thumbnail_path.to_string_lossy().to_string());
+    }
+    generate_thumbnail(primary, THUMBNAIL_MAX_DIM).ok()
+}
+
+pub fn load_rgba_image(path: &Path) -> AppResult<RgbaImage> {
+    if !path.exists() {
+        return Err(AppError::msg(format!(
+            "image path not found: {}",
+            path.display()
+        )));
+    }
+
+    let bytes = fs::read(path)?;
+    Ok(image::load_from_memory(&bytes)?.into_rgba8())
+}
+
+pub fn data_url_dimensions(data_url: &str) -> AppResult<(u32, u32)> {
+    let parsed = parse_data_url(data_url)?;
+    let dimensions = image::io::Reader::new(std::io::Cursor::new(parsed.bytes))
+        .with_guessed_format()?
+        .into_dimensions()?;
+    Ok(dimensions)
+}
+
+pub fn aspect_mismatches(width: u32, height: u32, target: f64) -> bool {
+    if width == 0 || height == 0 || target <= 0.0 {
+        return false;
+    }
+
+    let actual = width as f64 / height as f64;
+    (actual / target - 1.0).abs() > ASPECT_MISMATCH_TOLERANCE
+}
+
+/// Centers the image on a transparent canvas grown along one axis so the grid math for the
+/// requested aspect ratio holds again.
+pub fn pad_to_aspect(image: &RgbaImage, target: f64) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let actual = width as f64 / height as f64;
+    let (canvas_width, canvas_height) = if actual < target {
+        ((height as f64 * target).round() as u32, height)
+    } else {
+        (width, (width as f64 / target).round() as u32)
+    };
+
+    let mut canvas = RgbaImage::new(canvas_width.max(width), canvas_height.max(height));
+    let offset_x = (canvas.width() - width) / 2;
+    let offset_y = (canvas.height() - height) / 2;
+    imageops::replace(&mut canvas, image, offset_x as i64, offset_y as i64);
+    canvas
+}
+
+/// Picks the integer factor N whose output long edge lands closest to `target_long_edge`,
+/// considering only factors that divide the image and, for sheets, every grid cell.
+pub fn integer_downscale_factor(
+    width: u32,
+    height: u32,
     sprite_grid: Option<(u32, u32)>,
+    target_long_edge: u32,
+) -> Option<u32> {
+    let mut common = gcd(width, height);
+    if let Some((rows, cols)) = sprite_grid {
+        for cell in grid_cell_rects(width, height, rows, cols) {
+            common = gcd(common, gcd(cell.width, cell.height));
+        }
+    }
+
+    let long_edge = width.max(height);
+    (2..=common)
+        .filter(|factor| common.is_multiple_of(*factor))
+        .min_by_key(|factor| (long_edge / factor).abs_diff(target_long_edge))
+}
+
+/// Averages each NxN block into one pixel, weighting color by alpha so transparent
+/// neighbors don't darken sprite edges.
+pub fn box_downscale(image: &RgbaImage, factor: u32) -> RgbaImage {
+    let factor = factor.max(1);
+    let out_width = (image.width() / factor).max(1);
+    let out_height = (image.height() / factor).max(1);
+
+    RgbaImage::from_fn(out_width, out_height, |out_x, out_y| {
+        let mut color = [0_u64; 3];
+        let mut alpha = 0_u64;
+        let mut samples = 0_u64;
+
+        for y in out_y * factor..((out_y + 1) * factor).min(image.height()) {
+            for x in out_x * factor..((out_x + 1) * factor).min(image.width()) {
+                let [r, g, b, a] = image.get_pixel(x, y).0;
+                color[0] += r as u64 * a as u64;
+                color[1] += g as u64 * a as u64;
+                color[2] += b as u64 * a as u64;
+                alpha += a as u64;
+                samples += 1;
+            }
+        }
+
+        if alpha == 0 {
+            return image::Rgba([0, 0, 0, 0]);
+        }
+
+        image::Rgba([
+            (color[0] / alpha) as u8,
+            (color[1] / alpha) as u8,
+            (color[2] / alpha) as u8,
+            (alpha / samples.max(1)) as u8,
+        ])
+    })
+}
+
+pub fn resize_to_long_edge(image: &RgbaImage, target_long_edge: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let long_edge = width.max(height).max(1);
+    let scale = target_long_edge.max(1) as f64 / long_edge as f64;
+    let new_width = ((width as f64 * scale).round() as u32).max(1);
+    let new_height = ((height as f64 * scale).round() as u32).max(1);
+
+    imageops::resize(image, new_width, new_height, imageops::FilterType::Triangle)
+}
+
+const NEUQUANT_SAMPLE_FACTOR: i32 = 10;
+
+/// Reduces the opaque pixels to at most `max_colors` (clamped to 2-256) colors with NeuQuant.
+/// Fully transparent pixels are left out of training and alpha is never changed, so keyed
+/// backgrounds don't take up palette entries.
+pub fn quantize_to_palette(image: &mut RgbaImage, max_colors: u32) {
+    let samples: Vec<u8> = image
+        .pixels()
+        .filter(|pixel| pixel[3] > 0)
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+        .collect();
+    if samples.is_empty() {
+        return;
+    }
+
+    let colors = max_colors.clamp(2, 256) as usize;
+    let quantizer = color_quant::NeuQuant::new(NEUQUANT_SAMPLE_FACTOR, colors, &samples);
+    for pixel in image.pixels_mut() {
+        if pixel[3] == 0 {
+            continue;
+        }
+
+        let index = quantizer.index_of(&[pixel[0], pixel[1], pixel[2], 255]);
+        if let Some([r, g, b, _]) = quantizer.lookup(index) {
+            pixel[0] = r;
+            pixel[1] = g;
+            pixel[2] = b;
+        }
+    }
+}
+
+/// Quantizes the non-transparent pixels to at most `max_colors` and reports each resulting
+/// color's coverage, most common first. A fully transparent image has no palette.
+pub fn extract_palette(image: &RgbaImage, max_colors: u32) -> Vec<PaletteSwatch> {
+    let samples: Vec<u8> = image
+        .pixels()
+        .filter(|pixel| pixel[3] > 0)
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+        .collect();
+    let visible = samples.len() / 4;
+    if visible == 0 {
+        return Vec::new();
+    }
+
+    let colors = max_colors.clamp(2, 256) as usize;
+    let quantizer = color_quant::NeuQuant::new(NEUQUANT_SAMPLE_FACTOR, colors, &samples);
+    let mut counts = vec![0usize; colors];
+    for sample in samples.chunks_exact(4) {
+        counts[quantizer.index_of(sample)] += 1;
+    }
+
+    let mut swatches: Vec<PaletteSwatch> = counts
+        .into_iter()
+        .enumerate()
+        .filter(|(_, count)| *count > 0)
+        .filter_map(|(index, count)| {
+            let [r, g, b, _] = quantizer.lookup(index)?;
+            Some(PaletteSwatch {
+                rgb: [r, g, b],
+                fraction: count as f64 / visible as f64,
+            })
+        })
+        .collect();
+    swatches.sort_by(|a, b| b.fraction.total_cmp(&a.fraction));
+    swatches.truncate(max_colors as usize);
+    swatches
+}
+
+/// Replaces every pixel within `tolerance` (per channel) of a mapping's `from` color with its
+/// `to` color, taking the closest mapping when several match. Alpha is left untouched.
+pub fn recolor(image: &RgbaImage, mappings: &[ColorMapping], tolerance: u8) -> RgbaImage {
+    let mut output = image.clone();
+    for pixel in output.pixels_mut() {
+        let rgb = [pixel[0], pixel[1], pixel[2]];
+        let closest = mappings
+            .iter()
+            .map(|mapping| {
+                let difference = rgb
+                    .iter()
+                    .zip(mapping.from)
+                    .map(|(value, from)| value.abs_diff(from))
+                    .max()
+                    .unwrap_or(0);
+                (difference, mapping)
+            })
+            .filter(|(difference, _)| *difference <= tolerance)
+            .min_by_key(|(difference, _)| *difference);
+
+        if let Some((_, mapping)) = closest {
+            pixel[0] = mapping.to[0];
+            pixel[1] = mapping.to[1];
+            pixel[2] = mapping.to[2];
+        }
+    }
+
+    output
+}
+
+/// Paints `color` onto every fully transparent pixel within `thickness` (Euclidean) of a
+/// non-transparent one. A thickness of 0 leaves the image unchanged.
+pub fn add_outline(image: &mut RgbaImage, color: [u8; 4], thickness: u32) {
+    if thickness == 0 {
+        return;
+    }
+
+    let (width, height) = image.dimensions();
+    let source = image.clone();
+    let reach = thickness as i64;
+    let is_subject = |x: i64, y: i64| {
+        x >= 0
+            && y >= 0
+            && x < width as i64
+            && y < height as i64
+            && source.get_pixel(x as u32, y as u32)[3] > 0
+    };
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        if pixel[3] != 0 {
+            continue;
+        }
+
+        let (x, y) = (x as i64, y as i64);
+        let touches_subject = (-reach..=reach).any(|dy| {
+            (-reach..=reach)
+                .any(|dx| dx * dx + dy * dy <= reach * reach && is_subject(x + dx, y + dy))
+        });
+        if touches_subject {
+            *pixel = image::Rgba(color);
+        }
+    }
+}
+
+/// Runs `add_outline` on each grid cell separately so outlines never spill into a
+/// neighboring frame.
+pub fn add_outline_per_frame(
+    image: &mut RgbaImage,
+    grid: FrameGrid,
+    color: [u8; 4],
+    thickness: u32,
+) {
+    for (cell, mut frame) in sheet_frames(image, grid) {
+        add_outline(&mut frame, color, thickness);
+        imageops::replace(image, &frame, cell.x as i64, cell.y as i64);
+    }
+}
+
+/// Copies out `rect`, which must be non-empty and lie entirely inside the image.
+pub fn crop(image: &RgbaImage, rect: Rect) -> AppResult<RgbaImage> {
+    if rect.width == 0 || rect.height == 0 {
+        return Err(AppError::invalid("crop width and height must be > 0"));
+    }
+    let (width, height) = image.dimensions();
+    let fits = rect
+        .x
+        .checked_add(rect.width)
+        .is_some_and(|right| right <= width)
+        && rect
+            .y
+            .checked_add(rect.height)
+            .is_some_and(|bottom| bottom <= height);
+    if !fits {
+        return Err(AppError::invalid(format!(
+            "crop {}x{} at ({}, {}) falls outside the {width}x{height} image",
+            rect.width, rect.height, rect.x, rect.y
+        )));
+    }
+
+    Ok(imageops::crop_imm(image, rect.x, rect.y, rect.width, rect.height).to_image())
+}
+
+pub fn apply_transform(image: &RgbaImage, transform: ImageTransform) -> RgbaImage {
+    match transform {
+        ImageTransform::FlipHorizontal => imageops::flip_horizontal(image),
+        ImageTransform::FlipVertical => imageops::flip_vertical(image),
+        ImageTransform::Rotate90 => imageops::rotate90(image),
+        ImageTransform::Rotate180 => imageops::rotate180(image),
+        ImageTransform::Rotate270 => imageops::rotate270(image),
+    }
+}
+
+/// Tiles `frames` row-major onto a transparent `rows` x `cols` sheet. Cells are sized to the
+/// largest frame and smaller frames are centered in theirs rather than stretched.
+pub fn assemble_sheet(frames: &[RgbaImage], rows: u32, cols: u32) -> AppResult<RgbaImage> {
+    place_frames(frames, rows, cols).map(|(sheet, _)| sheet)
+}
+
+/// `assemble_sheet`, also returning the rectangle each frame was drawn at.
+fn place_frames(frames: &[RgbaImage], rows: u32, cols: u32) -> AppResult<(RgbaImage, Vec<Rect>)> {
+    if frames.is_empty() || rows == 0 || cols == 0 {
+        return Err(AppError::invalid(
+            "need at least one frame and a non-empty grid",
+        ));
+    }
+    let too_large = || AppError::invalid(format!("a {rows}x{cols} grid is too large"));
+    let capacity = rows.checked_mul(cols).ok_or_else(too_large)?;
+    if frames.len() > capacity as usize {
+        return Err(AppError::invalid(format!(
+            "{} frames don't fit a {rows}x{cols} grid",
+            frames.len()
+        )));
+    }
+
+    let cell_width = frames.iter().map(RgbaImage::width).max().unwrap_or(0);
+    let cell_height = frames.iter().map(RgbaImage::height).max().unwrap_or(0);
+    let sheet_width = cell_width.checked_mul(cols).ok_or_else(too_large)?;
+    let sheet_height = cell_height.checked_mul(rows).ok_or_else(too_large)?;
+    let mut sheet = RgbaImage::new(sheet_width, sheet_height);
+    let mut placements = Vec::with_capacity(frames.len());
+    for (index, frame) in frames.iter().enumerate() {
+        let (row, col) = (index as u32 / cols, index as u32 % cols);
+        let x = col * cell_width + (cell_width - frame.width()) / 2;
+        let y = row * cell_height + (cell_height - frame.height()) / 2;
+        imageops::replace(&mut sheet, frame, x as i64, y as i64);
+        placements.push(Rect {
+            x,
+            y,
+            width: frame.width(),
+            height: frame.height(),
+        });
+    }
+    Ok((sheet, placements))
+}
+
+/// Trims each frame to its opaque content and re-lays them, in play order, row-major on the
+/// same grid with every cell sized to the largest trimmed frame. Uneven spacing in the source
+/// sheet disappears; the returned frames record where each one moved from and to.
+pub fn repack_sheet(
+    image: &RgbaImage,
+    grid: FrameGrid,
+) -> AppResult<(RgbaImage, Vec<RepackedFrame>)> {
+    let (trimmed, sources): (Vec<_>, Vec<_>) = sheet_frames(image, grid)
+        .into_iter()
+        .map(|(cell, frame)| {
+            let (trimmed, content) = trim_transparent(&frame);
+            let source = Rect {
+                x: cell.x + content.x,
+                y: cell.y + content.y,
+                ..content
+            };
+            (trimmed, source)
+        })
+        .unzip();
+
+    let (sheet, placements) = place_frames(&trimmed, grid.rows, grid.cols)?;
+    let frames = sources
+        .into_iter()
+        .zip(placements)
+        .map(|(source, placed)| RepackedFrame {
+            source,
+            // Empty frames were drawn as a 1x1 transparent placeholder.
+            target: Rect {
+                width: source.width,
+                height: source.height,
+                ..placed
+            },
+        })
+        .collect();
+    Ok((sheet, frames))
+}
+
+/// Applies `transform` to each frame inside its own cell, so frames keep their place in the
+/// sheet. Rotating by 90 or 270 degrees needs square cells.
+pub fn transform_per_frame(
+    image: &mut RgbaImage,
+    grid: FrameGrid,
+    transform: ImageTransform,
+) -> AppResult<()> {
+    for (cell, frame) in sheet_frames(image, grid) {
+        if transform.swaps_axes() && cell.width != cell.height {
+            return Err(AppError::invalid(format!(
+                "cannot rotate {}x{} frames in place; cells must be square",
+                cell.width, cell.height
+            )));
+        }
+        let frame = apply_transform(&frame, transform);
+        imageops::replace(image, &frame, cell.x as i64, cell.y as i64);
+    }
+    Ok(())
+}
+
+/// Composites the image over a solid color, for targets that mishandle transparency.
+pub fn flatten_onto_color(image: &mut RgbaImage, color: [u8; 4]) {
+    let background_alpha = color[3] as u32;
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u32;
+        let inverse = 255 - alpha;
+        for channel in 0..3 {
+            let blended = pixel[channel] as u32 * alpha + color[channel] as u32 * inverse;
+            pixel[channel] = ((blended + 127) / 255) as u8;
+        }
+        pixel[3] = (alpha + (background_alpha * inverse + 127) / 255) as u8;
+    }
+}
+
+/// Longest edge an upscaled export may have. Allocating past this (a 4K image at 16x is
+/// about 17 GB of RGBA) would abort the process rather than fail cleanly.
+const MAX_UPSCALED_EDGE: u32 = 16384;
+
+/// Scales `image` up by a whole `factor`, refusing outputs longer than [`MAX_UPSCALED_EDGE`].
+pub fn upscale(image: &RgbaImage, factor: u32, filter: ScaleFilter) -> AppResult<RgbaImage> {
+    let too_large = || {
+        AppError::invalid(format!(
+            "scaling {}x{} by {factor} would exceed the {MAX_UPSCALED_EDGE}px export limit",
+            image.width(),
+            image.height()
+        ))
+    };
+    let width = image.width().checked_mul(factor).ok_or_else(too_large)?;
+    let height = image.height().checked_mul(factor).ok_or_else(too_large)?;
+    if width.max(height) > MAX_UPSCALED_EDGE {
+        return Err(too_large());
+    }
+
+    Ok(imageops::resize(
+        image,
+        width,
+        height,
+        resize_filter(filter),
+    ))
+}
+
+fn resize_filter(filter: ScaleFilter) -> imageops::FilterType {
+    match filter {
+        ScaleFilter::Nearest => imageops::FilterType::Nearest,
+        ScaleFilter::Linear => imageops::FilterType::Triangle,
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+pub fn image_file_name(child_id: &str, index: usize, kind: ImageKind) -> String {
+    match kind {
+        ImageKind::Output => format!("{child_id}_{index}.png"),
+        ImageKind::Raw => format!("{child_id}_{index}.raw.png"),
+        ImageKind::Thumbnail => format!("{child_id}_{index}_thumb.png"),
+        ImageKind::Frame(frame) => format!("{child_id}_{index}_frame_{frame}.png"),
+    }
+}
+
+pub fn load_edit_mask(base_data_url: &str, mask_data_url: &str) -> AppResult<EditMask> {
+    let base_bytes = parse_data_url(base_data_url)?;
+    let mask_bytes = parse_data_url(mask_data_url)?;
+    let base = image::load_from_memory(&base_bytes.bytes)?.into_rgba8();
+    let mask = image::load_from_memory(&mask_bytes.bytes)?;
+
+    if mask.dimensions() != base.dimensions() {
+        return Err(AppError::invalid(format!(
+            "mask dimensions {}x{} do not match base image dimensions {}x{}",
+            mask.width(),
+            mask.height(),
+            base.width(),
+            base.height()
+        )));
+    }
+
+    let mask = mask.into_rgba8();
+    let weights = GrayImage::from_fn(mask.width(), mask.height(), |x, y| {
+        let [r, g, b, a] = mask.get_pixel(x, y).0;
+        let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+        image::Luma([((luma * a as u32) / 255) as u8])
+    });
+
+    Ok(EditMask { base, weights })
+}
+
+pub fn preserve_frames(
+    base_image_path: &Path,
+    grid: FrameGrid,
+    frame_indices: &[usize],
+) -> AppResult<PreservedRegions> {
+    let base = load_rgba_image(base_image_path)?;
+    let frames = frame_rects(base.width(), base.height(), grid);
+    let regions = frame_indices
+        .iter()
+        .map(|index| {
+            frames.get(*index).copied().ok_or_else(|| {
+                AppError::invalid(format!(
+                    "frame {index} is out of range for a sheet with {} frames",
+                    frames.len()
+                ))
+            })
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+
+    Ok(PreservedRegions { base, regions })
+}
+
+fn restore_preserved_regions(image: &RgbaImage, preserved: &PreservedRegions) -> RgbaImage {
+    let (width, height) = preserved.base.dimensions();
+    let mut restored = if image.dimensions() == (width, height) {
+        image.clone()
+    } else {
+        imageops::resize(image, width, height, imageops::FilterType::Lanczos3)
+    };
+
+    for region in &preserved.regions {
+        let cell = imageops::crop_imm(
+            &preserved.base,
+            region.x,
+            region.y,
+            region.width,
+            region.height,
+        );
+        imageops::replace(&mut restored, &*cell, region.x as i64, region.y as i64);
+    }
+
+    restored
+}
+
+fn composite_with_edit_mask(result: &RgbaImage, edit_mask: &EditMask) -> RgbaImage {
+    let (width, height) = edit_mask.base.dimensions();
+    let resized;
+    let result = if result.dimensions() == (width, height) {
+        result
+    } else {
+        resized = imageops::resize(result, width, height, imageops::FilterType::Lanczos3);
+        &resized
+    };
+
+    let mut composite = edit_mask.base.clone();
+    for (x, y, pixel) in composite.enumerate_pixels_mut() {
+        let weight = edit_mask.weights.get_pixel(x, y).0[0] as u32;
+        if weight == 0 {
+            continue;
+        }
+
+        let edited = result.get_pixel(x, y).0;
+        for (base, edited) in pixel.0.iter_mut().zip(edited) {
+            *base = ((*base as u32 * (255 - weight) + edited as u32 * weight + 127) / 255) as u8;
+        }
+    }
+
+    composite
+}
+
+pub fn validate_data_url(data_url: &str) -> AppResult<()> {
+    parse_data_url(data_url).map(|_| ())
+}
+
+pub fn parse_data_url(data_url: &str) -> AppResult<ParsedDataUrl> {
+    if !data_url.starts_with("data:") {
+        return Err(AppError::invalid("expected a data URL with image payload"));
+    }
+
+    let (metadata, payload) = data_url
+        .split_once(',')
+        .ok_or_else(|| AppError::invalid("invalid data URL format"))?;
+
+    if !metadata.contains(";base64") {
+        return Err(AppError::invalid("data URL must be base64 encoded"));
+    }
+
+    let mime = metadata
+        .trim_start_matches("data:")
+        .split(';')
+        .next()
+        .unwrap_or_default();
+    if !SUPPORTED_MIMES.contains(&mime) {
+        return Err(AppError::invalid(format!(
+            "unsupported image mime type: {mime}. allowed: png/jpeg/webp"
+        )));
+    }
+
+    let bytes = STANDARD.decode(payload.trim())?;
+    Ok(ParsedDataUrl { bytes })
+}
+
+/// Reference payloads up to this many bytes are sent untouched.
+const REFERENCE_SHRINK_THRESHOLD_BYTES: usize = 4 * 1024 * 1024;
+
+/// Shrinks a reference image over the size threshold: downscaled to `max_long_edge` when
+/// larger, then re-encoded as optimized PNG or JPEG. The original is kept if shrinking
+/// doesn't actually make it smaller.
+pub fn shrink_reference_data_url(
+    data_url: &str,
+    compression: ReferenceCompression,
+) -> AppResult<String> {
+    let parsed = parse_data_url(data_url)?;
+    if parsed.bytes.len() <= REFERENCE_SHRINK_THRESHOLD_BYTES {
+        return Ok(data_url.to_string());
+    }
+
+    let mut image = image::load_from_memory(&parsed.bytes)?.into_rgba8();
+    if image.width().max(image.height()) > compression.max_long_edge {
+        image = resize_to_long_edge(&image, compression.max_long_edge);
+    }
+    let (mime, bytes) = match compression.jpeg_quality {
+        Some(quality) => {
+            flatten_onto_color(&mut image, [255, 255, 255, 255]);
+            let rgb = image::DynamicImage::ImageRgba8(image).into_rgb8();
+            let mut bytes = Vec::new();
+            JpegEncoder::new_with_quality(&mut bytes, quality.clamp(1, 100)).write_image(
+                rgb.as_raw(),
+                rgb.width(),
+                rgb.height(),
+                ColorType::Rgb8,
+            )?;
+            ("image/jpeg", bytes)
+        }
+        None => (
+            "image/png",
+            encode_png_optimized(image.as_raw(), image.width(), image.height())?,
+        ),
+    };
+    if bytes.len() >= parsed.bytes.len() {
+        return Ok(data_url.to_string());
+    }
+
+    Ok(format!("data:{mime};base64,{}", STANDARD.encode(bytes)))
+}
+
+/// Validates a user-supplied reference image, transcoding gif/bmp/tiff to PNG so the API
+/// accepts it. Animated GIFs keep only their first frame.
+pub fn normalize_input_data_url(data_url: &str) -> AppResult<String> {
+    let transcode_format = data_url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split([';', ',']).next())
+        .and_then(|mime| {
+            TRANSCODED_MIMES
+                .iter()
+                .find(|(candidate, _)| candidate.eq_ignore_ascii_case(mime))
+        })
+        .map(|(_, format)| *format);
+    let Some(format) = transcode_format else {
+        validate_data_url(data_url)?;
+        return Ok(data_url.to_string());
+    };
+
+    let (metadata, payload) = data_url
+        .split_once(',')
+        .ok_or_else(|| AppError::invalid("invalid data URL format"))?;
+    if !metadata.contains(";base64") {
+        return Err(AppError::invalid("data URL must be base64 encoded"));
+    }
+    let bytes = STANDARD.decode(payload.trim())?;
+    let image = image::load_from_memory_with_format(&bytes, format)?.into_rgba8();
+    Ok(format!(
+        "data:image/png;base64,{}",
+        STANDARD.encode(encode_png_optimized(
+            image.as_raw(),
+            image.width(),
+            image.height()
+        )?)
+    ))
+}
+
+/// Compares two images pixel by pixel. The smaller one (by area) is resized to the larger's
+/// dimensions first. A pixel counts as changed when any channel differs by more than
+/// `threshold`.
+pub fn diff_images(
+    a: &RgbaImage,
+    b: &RgbaImage,
+    threshold: u8,
+    visualize: bool,
+) -> AppResult<ImageDiff> {
+    let area = |image: &RgbaImage| image.width() as u64 * image.height() as u64;
+    let (width, height) = if area(a) >= area(b) {
+        a.dimensions()
+    } else {
+        b.dimensions()
+    };
+    let fit = |image: &RgbaImage| {
+        if image.dimensions() == (width, height) {
+            image.clone()
+        } else {
+            imageops::resize(image, width, height, imageops::FilterType::Lanczos3)
+        }
+    };
+    let (a, b) = (fit(a), fit(b));
+
+    let mut changed = 0u64;
+    let mut delta_sum = 0u64;
+    let mut visualization = visualize.then(|| RgbaImage::new(width, height));
+    for ((x, y, pa), pb) in a.enumerate_pixels().zip(b.pixels()) {
+        let deltas = [0, 1, 2, 3].map(|channel| pa[channel].abs_diff(pb[channel]));
+        delta_sum += deltas.iter().map(|delta| *delta as u64).sum::<u64>();
+        let is_changed = deltas.iter().any(|delta| *delta > threshold);
+        if is_changed {
+            changed += 1;
+        }
+        if let Some(visualization) = visualization.as_mut() {
+            let pixel = if is_changed {
+                image::Rgba([255, 0, 0, 255])
+            } else {
+                let luma = (pa[0] as u32 * 299 + pa[1] as u32 * 587 + pa[2] as u32 * 114) / 1000;
+                // Dimmed so the red regions stand out.
+                let gray = (luma / 2) as u8;
+                image::Rgba([gray, gray, gray, 255])
+            };
+            visualization.put_pixel(x, y, pixel);
+        }
+    }
+
+    let pixel_count = (width as u64 * height as u64).max(1) as f64;
+    let diff_data_url = visualization
+        .map(|image| -> AppResult<String> {
+            let png = encode_png_optimized(image.as_raw(), width, height)?;
+            Ok(format!("data:image/png;base64,{}", STANDARD.encode(png)))
+        })
+        .transpose()?;
+    Ok(ImageDiff {
+        width,
+        height,
+        changed_percent: changed as f64 / pixel_count * 100.0,
+        mean_delta: delta_sum as f64 / (pixel_count * 4.0),
+        diff_data_url,
+    })
+}
+
+pub fn read_image_path_as_data_url(path: &Path) -> AppResult<String> {
+    if !path.exists() {
+        return Err(AppError::invalid(format!(
+            "image path not found: {}",
+            path.display()
+        )));
+    }
+
+    let bytes = fs::read(path)?;
+    let mime = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        _ => "image/png",
+    };
+
+    Ok(format!("data:{mime};base64,{}", STANDARD.encode(bytes)))
+}
+
+pub fn export_image_to_path(
+    source_image_path: &Path,
+    destination_path: &Path,
+    remove_chromakey_background: bool,
+    chromakey_color: Option<[u8; 3]>,
+    scale: Option<(u32, ScaleFilter)>,
+    format: OutputFormat,
+    background_color: Option<[u8; 4]>,
+) -> AppResult<String> {
+    if !source_image_path.exists() {
+        return Err(AppError::invalid(format!(
+            "source image path not found: {}",
+            source_image_path.display()
+        )));
+    }
+
+    let scale = scale.filter(|(factor, _)| *factor > 1);
+    let reencode = remove_chromakey_background
+        || scale.is_some()
+        || format != OutputFormat::Png
+        || background_color.is_some();
+    let mut output_path = destination_path.to_path_buf();
+    if reencode || output_path.extension().is_none() {
+        output_path.set_extension(format.extension());
+    }
+
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if reencode {
+        let source_bytes = fs::read(source_image_path)?;
+        let mut image = image::load_from_memory(&source_bytes)?.into_rgba8();
+        if remove_chromakey_background {
+            let key = Keyer::new(
+                chromakey_color.unwrap_or(DEFAULT_CHROMAKEY_COLOR),
+                ChromakeySettings::default(),
+            );
+            apply_export_chromakey_transparency(&mut image, key);
+        }
+        if let Some(color) = background_color {
+            flatten_onto_color(&mut image, color);
+        }
+        if let Some((factor, filter)) = scale {
+            image = upscale(&image, factor, filter)?;
+        }
+        fs::write(&output_path, encode_image(&image, format)?)?;
+    } else {
+        fs::copy(source_image_path, &output_path)?;
+    }
+
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+/// Encodes RGBA pixels, keeping the alpha channel in either format.
+pub fn encode_image(image: &RgbaImage, format: OutputFormat) -> AppResult<Vec<u8>> {
+    match format {
+        OutputFormat::Png => encode_png_optimized(image.as_raw(), image.width(), image.height()),
+        OutputFormat::WebP => {
+            let mut bytes = Vec::new();
+            WebPEncoder::new_lossless(&mut bytes).encode(
+                image.as_raw(),
+                image.width(),
+                image.height(),
+                ColorType::Rgba8,
+            )?;
+            Ok(bytes)
+        }
+    }
+}
+
+/// Slices the sheet into frames, stages them next to `watch_dir`, and only then moves them
+/// into place so a watching engine never observes a half-written frame.
+pub fn export_frames_to_watch_folder(
+    source_image_path: &Path,
+    grid: FrameGrid,
+    watch_dir: &Path,
+    file_prefix: &str,
+    clear_stale: bool,
+) -> AppResult<Vec<String>> {
+    if !source_image_path.exists() {
+        return Err(AppError::invalid(format!(
+            "source image path not found: {}",
+            source_image_path.display()
+        )));
+    }
+
+    fs::create_dir_all(watch_dir)?;
+    let watch_dir_name = watch_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "frames".to_string());
+    let staging_dir = watch_dir
+        .parent()
+        .unwrap_or(watch_dir)
+        .join(format!(".{watch_dir_name}.staging-{}", Uuid::new_v4()));
+    fs::create_dir_all(&staging_dir)?;
+
+    let result = stage_and_swap_frames(
+        source_image_path,
+        grid,
+        &staging_dir,
+        watch_dir,
+        file_prefix,
+        clear_stale,
+    );
+    let _ = fs::remove_dir_all(&staging_dir);
+    result
+}
+
+fn stage_and_swap_frames(
+    source_image_path: &Path,
+    grid: FrameGrid,
+    staging_dir: &Path,
+    watch_dir: &Path,
+    file_prefix: &str,
+    clear_stale: bool,
+) -> AppResult<Vec<String>> {
+    let source_bytes = fs::read(source_image_path)?;
+    let image = image::load_from_memory(&source_bytes)?.into_rgba8();
+
+    let mut frame_names = Vec::new();
+    for (frame_index, (_, frame)) in sheet_frames(&image, grid).into_iter().enumerate() {
+        let frame_name = format!("{file_prefix}_{frame_index:03}.png");
+        let png_bytes = encode_png_optimized(frame.as_raw(), frame.width(), frame.height())?;
+        fs::write(staging_dir.join(&frame_name), png_bytes)?;
+        frame_names.push(frame_name);
+    }
+
+    if clear_stale {
+        let stale_prefix = format!("{file_prefix}_");
+        for entry in fs::read_dir(watch_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with(&stale_prefix)
+                && name.ends_with(".png")
+                && !frame_names.contains(&name)
+            {
+                fs::remove_file(entry.path())?;
+            }
+        }
+    }
+
+    let mut exported = Vec::new();
+    for frame_name in frame_names {
+        let destination = watch_dir.join(&frame_name);
+        rename_replacing(&staging_dir.join(&frame_name), &destination)?;
+        exported.push(destination.to_string_lossy().to_string());
+    }
+
+    Ok(exported)
+}
+
+/// Cuts a sheet into its grid cells and writes each one into the project's images dir,
+/// optionally trimmed to its visible pixels. Frames are returned in play order.
+pub fn slice_sprite_sheet(
+    app: &AppHandle,
+    project_id: &str,
+    child_id: &str,
+    index: usize,
+    source_image_path: &Path,
+    grid: FrameGrid,
+    trim: bool,
+) -> AppResult<SlicedSheet> {
+    let image = load_rgba_image(source_image_path)?;
+    let images_dir = images_dir(app, project_id)?;
+
+    let mut frames = Vec::new();
+    for (frame_index, (cell, frame)) in sheet_frames(&image, grid).into_iter().enumerate() {
+        let (frame, content) = if trim {
+            trim_transparent(&frame)
+        } else {
+            let content = Rect {
+                x: 0,
+                y: 0,
+                width: cell.width,
+                height: cell.height,
+            };
+            (frame, content)
+        };
+
+        let file_name = image_file_name(child_id, index, ImageKind::Frame(frame_index));
+        let frame_path = images_dir.join(file_name);
+        let png_bytes = encode_png_optimized(frame.as_raw(), frame.width(), frame.height())?;
+        fs::write(&frame_path, png_bytes)?;
+        frames.push(SlicedFrame {
+            path: frame_path.to_string_lossy().to_string(),
+            cell,
+            content,
+        });
+    }
+
+    Ok(SlicedSheet {
+        frames,
+        grid_warning: validate_sprite_grid(image.width(), image.height(), grid.rows, grid.cols),
+    })
+}
+
+/// Encodes the sheet's frames, in play order, as a looping GIF. Frames are flattened onto
+/// `background` when given; otherwise pixels under half alpha become the GIF's transparent
+/// color and each frame clears before the next so transparent areas don't show stale frames.
+pub fn export_sprite_gif(
+    source_image_path: &Path,
+    grid: FrameGrid,
+    destination_path: &Path,
+    fps: u32,
+    background: Option<[u8; 3]>,
+) -> AppResult<String> {
+    let image = load_rgba_image(source_image_path)?;
+    let frames = sheet_frames(&image, grid);
+    let (canvas_width, canvas_height) = frames.iter().fold((0, 0), |(width, height), (cell, _)| {
+        (width.max(cell.width), height.max(cell.height))
+    });
+    let (Ok(gif_width), Ok(gif_height)) =
+        (u16::try_from(canvas_width), u16::try_from(canvas_height))
+    else {
+        return Err(AppError::invalid(format!(
+            "{canvas_width}x{canvas_height} frames are too large for a GIF"
+        )));
+    };
+    if gif_width == 0 || gif_height == 0 {
+        return Err(AppError::invalid("sprite sheet has no frames to export"));
+    }
+
+    let mut output_path = destination_path.to_path_buf();
+    output_path.set_extension("gif");
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let delay = ((100.0 / fps.max(1) as f64).round() as u16).max(1);
+    let writer = std::io::BufWriter::new(fs::File::create(&output_path)?);
+    let mut encoder = gif::Encoder::new(writer, gif_width, gif_height, &[]).map_err(gif_error)?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(gif_error)?;
+
+    for (_, frame) in frames {
+        let mut canvas = RgbaImage::new(canvas_width, canvas_height);
+        match background {
+            Some([r, g, b]) => {
+                for pixel in canvas.pixels_mut() {
+                    *pixel = image::Rgba([r, g, b, 255]);
+                }
+                imageops::overlay(&mut canvas, &frame, 0, 0);
+            }
+            None => {
+                imageops::replace(&mut canvas, &frame, 0, 0);
+                for pixel in canvas.pixels_mut() {
+                    *pixel = if pixel[3] < 128 {
+                        image::Rgba([0, 0, 0, 0])
+                    } else {
+                        image::Rgba([pixel[0], pixel[1], pixel[2], 255])
+                    };
+                }
+            }
+        }
+
+        let mut pixels = canvas.into_raw();
+        let mut gif_frame = gif::Frame::from_rgba_speed(gif_width, gif_height, &mut pixels, 10);
+        gif_frame.delay = delay;
+        gif_frame.dispose = gif::DisposalMethod::Background;
+        encoder.write_frame(&gif_frame).map_err(gif_error)?;
+    }
+
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+/// Resizes every cell to exactly `frame_width` x `frame_height` and writes the cells back in
+/// their original positions as a `cols * frame_width` by `rows * frame_height` PNG.
+pub fn export_sheet_at_frame_size(
+    source_image_path: &Path,
+    grid: FrameGrid,
+    frame_width: u32,
+    frame_height: u32,
+    filter: ScaleFilter,
+    destination_path: &Path,
 ) -> AppResult<String> {
-    let image_bytes = parse_data_url(data_url)?;
-    let mut image = image::load_from_memory(&image_bytes.bytes)?.into_rgba8();
-    if apply_chromakey {
-        apply_chromakey_transparency(&mut image, sprite_grid);
+    let image = load_rgba_image(source_image_path)?;
+    let mut sheet = RgbaImage::new(grid.cols * frame_width, grid.rows * frame_height);
+    let cells = grid_cell_rects(image.width(), image.height(), grid.rows, grid.cols);
+    for (index, cell) in cells.into_iter().enumerate() {
+        let frame = imageops::crop_imm(&image, cell.x, cell.y, cell.width, cell.height).to_image();
+        let frame = imageops::resize(&frame, frame_width, frame_height, resize_filter(filter));
+        let (row, col) = (index as u32 / grid.cols, index as u32 % grid.cols);
+        imageops::replace(
+            &mut sheet,
+            &frame,
+            (col * frame_width) as i64,
+            (row * frame_height) as i64,
+        );
     }
-    let image_path = images_dir(app, project_id)?.join(format!("{child_id}_{index}.png"));
 
-    let png_bytes = encode_png_optimized(image.as_raw(), image.width(), image.height())?;
-    fs::write(&image_path, png_bytes)?;
+    let mut output_path = destination_path.to_path_buf();
+    output_path.set_extension("png");
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let png_bytes = encode_png_optimized(sheet.as_raw(), sheet.width(), sheet.height())?;
+    fs::write(&output_path, png_bytes)?;
 
-    Ok(image_path.to_string_lossy().to_string())
+    Ok(output_path.to_string_lossy().to_string())
 }
 
-pub fn validate_data_url(data_url: &str) -> AppResult<()> {
-    parse_data_url(data_url).map(|_| ())
+fn gif_error(error: gif::EncodingError) -> AppError {
+    AppError::msg(format!("failed to encode gif: {error}"))
 }
 
-pub fn parse_data_url(data_url: &str) -> AppResult<ParsedDataUrl> {
-    if !data_url.starts_with("data:") {
-        return Err(AppError::msg("expected a data URL with image payload"));
+const CONTACT_TILE_EDGE: u32 = 160;
+const CONTACT_GAP: u32 = 8;
+const CONTACT_BACKGROUND: [u8; 4] = [255, 255, 255, 255];
+/// Tiles get a light gray behind them so keyed sprites stand out from the sheet.
+const CONTACT_TILE_BACKGROUND: [u8; 4] = [236, 236, 236, 255];
+const CAPTION_COLOR: [u8; 4] = [48, 48, 48, 255];
+const CAPTION_SCALE: u32 = 2;
+const CAPTION_PADDING: u32 = 4;
+const CAPTION_HEIGHT: u32 = GLYPH_HEIGHT * CAPTION_SCALE + 2 * CAPTION_PADDING;
+
+/// Tiles every child's primary thumbnail row-major, `columns` wide, each captioned with the
+/// child's name, and writes the result to `contact_sheet.png` in the project dir. Children
+/// without a readable image get an empty tile so the captions still line up with the list.
+pub fn build_contact_sheet(app: &AppHandle, project_id: &str, columns: u32) -> AppResult<String> {
+    let project = load_project(app, project_id)?;
+    if project.children.is_empty() {
+        return Err(AppError::invalid(format!(
+            "project {project_id} has no children to put on a contact sheet"
+        )));
     }
 
-    let (metadata, payload) = data_url
-        .split_once(',')
-        .ok_or_else(|| AppError::msg("invalid data URL format"))?;
+    let count = project.children.len() as u32;
+    let columns = columns.clamp(1, count);
+    let rows = count.div_ceil(columns);
+    let cell_height = CONTACT_TILE_EDGE + CAPTION_HEIGHT;
+    let mut sheet = RgbaImage::from_pixel(
+        columns * CONTACT_TILE_EDGE + (columns + 1) * CONTACT_GAP,
+        rows * cell_height + (rows + 1) * CONTACT_GAP,
+        image::Rgba(CONTACT_BACKGROUND),
+    );
+
+    for (index, child) in project.children.iter().enumerate() {
+        let (row, col) = (index as u32 / columns, index as u32 % columns);
+        let x = CONTACT_GAP + col * (CONTACT_TILE_EDGE + CONTACT_GAP);
+        let y = CONTACT_GAP + row * (cell_height + CONTACT_GAP);
+
+        let mut tile = RgbaImage::from_pixel(
+            CONTACT_TILE_EDGE,
+            CONTACT_TILE_EDGE,
+            image::Rgba(CONTACT_TILE_BACKGROUND),
+        );
+        let thumbnail = child_thumbnail_path(child)
+            .and_then(|path| load_rgba_image(Path::new(&path)).ok())
+            .map(|image| {
+                if image.width().max(image.height()) > CONTACT_TILE_EDGE {
+                    resize_to_long_edge(&image, CONTACT_TILE_EDGE)
+                } else {
+                    image
+                }
+            });
+        if let Some(thumbnail) = thumbnail {
+            let offset_x = (CONTACT_TILE_EDGE - thumbnail.width()) / 2;
+            let offset_y = (CONTACT_TILE_EDGE - thumbnail.height()) / 2;
+            imageops::overlay(&mut tile, &thumbnail, offset_x as i64, offset_y as i64);
+        }
+        imageops::replace(&mut sheet, &tile, x as i64, y as i64);
+
+        draw_caption(
+            &mut sheet,
+            &child.name,
+            x + CAPTION_PADDING,
+            y + CONTACT_TILE_EDGE + CAPTION_PADDING,
+            CONTACT_TILE_EDGE - 2 * CAPTION_PADDING,
+        );
+    }
 
-    if !metadata.contains(";base64") {
-        return Err(AppError::msg("data URL must be base64 encoded"));
+    let path = project_dir(app, project_id)?.join("contact_sheet.png");
+    fs::write(
+        &path,
+        encode_png_optimized(sheet.as_raw(), sheet.width(), sheet.height())?,
+    )?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+/// A 5x7 bitmap font for captions, one byte per row with the leftmost pixel in bit 4.
+/// Lowercase letters are drawn as uppercase, spaces are blank, and anything else missing
+/// from the table is drawn as `?`.
+const CAPTION_GLYPHS: [(char, [u8; 7]); 46] = [
+    ('A', [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+    ('B', [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E]),
+    ('C', [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E]),
+    ('D', [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E]),
+    ('E', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F]),
+    ('F', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10]),
+    ('G', [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F]),
+    ('H', [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+    ('I', [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('J', [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C]),
+    ('K', [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11]),
+    ('L', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F]),
+    ('M', [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11]),
+    ('N', [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11]),
+    ('O', [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('P', [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10]),
+    ('Q', [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D]),
+    ('R', [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11]),
+    ('S', [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E]),
+    ('T', [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+    ('U', [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('V', [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04]),
+    ('W', [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A]),
+    ('X', [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11]),
+    ('Y', [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04]),
+    ('Z', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F]),
+    ('0', [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E]),
+    ('1', [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('2', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F]),
+    ('3', [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E]),
+    ('4', [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02]),
+    ('5', [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E]),
+    ('6', [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E]),
+    ('7', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+    ('8', [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E]),
+    ('9', [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C]),
+    ('-', [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00]),
+    ('_', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C]),
+    (':', [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00]),
+    ('#', [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A]),
+    ('(', [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02]),
+    (')', [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08]),
+    ('/', [0x01, 0x01, 0x02, 0x04, 0x08, 0x10, 0x10]),
+    ('+', [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00]),
+    ('?', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04]),
+];
+
+fn caption_glyph(c: char) -> [u8; 7] {
+    if c == ' ' {
+        return [0; 7];
     }
+    let c = c.to_ascii_uppercase();
+    CAPTION_GLYPHS
+        .iter()
+        .find(|(glyph, _)| *glyph == c)
+        .or_else(|| CAPTION_GLYPHS.iter().find(|(glyph, _)| *glyph == '?'))
+        .map(|(_, rows)| *rows)
+        .unwrap_or_default()
+}
 
-    let mime = metadata
-        .trim_start_matches("data:")
-        .split(';')
-        .next()
-        .unwrap_or_default();
-    if !SUPPORTED_MIMES.contains(&mime) {
-        return Err(AppError::msg(format!(
-            "unsupported image mime type: {mime}. allowed: png/jpeg/webp"
-        )));
+/// Draws `text` with its top-left at (x, y), cut to `max_width` with a trailing `..` when it
+/// doesn't fit.
+fn draw_caption(image: &mut RgbaImage, text: &str, x: u32, y: u32, max_width: u32) {
+    let advance = (GLYPH_WIDTH + 1) * CAPTION_SCALE;
+    let max_chars = (max_width / advance) as usize;
+    let mut chars = text.trim().chars().collect::<Vec<_>>();
+    if chars.len() > max_chars {
+        chars.truncate(max_chars.saturating_sub(2));
+        chars.extend(['.', '.']);
     }
 
-    let bytes = STANDARD.decode(payload.trim())?;
-    Ok(ParsedDataUrl { bytes })
+    for (index, c) in chars.into_iter().enumerate() {
+        let glyph_x = x + index as u32 * advance;
+        for (row, bits) in caption_glyph(c).into_iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+                    continue;
+                }
+                for dy in 0..CAPTION_SCALE {
+                    for dx in 0..CAPTION_SCALE {
+                        let px = glyph_x + col * CAPTION_SCALE + dx;
+                        let py = y + row as u32 * CAPTION_SCALE + dy;
+                        if px < image.width() && py < image.height() {
+                            image.put_pixel(px, py, image::Rgba(CAPTION_COLOR));
+                        }
+                    }
+                }
+            }
+        }
+    }
 }
 
-pub fn read_image_path_as_data_url(path: &Path) -> AppResult<String> {
-    if !path.exists() {
-        return Err(AppError::msg(format!(
-            "image path not found: {}",
-            path.display()
-        )));
+/// Copies the sheet to `{name}.png` in `destination_dir` and writes a matching `{name}.json`
+/// atlas whose frames are named like the watch-folder export (`{name}_000.png`, ...).
+pub fn export_atlas(
+    source_image_path: &Path,
+    grid: FrameGrid,
+    destination_dir: &Path,
+    name: &str,
+) -> AppResult<AtlasExport> {
+    let image = load_rgba_image(source_image_path)?;
+    fs::create_dir_all(destination_dir)?;
+
+    let image_name = format!("{name}.png");
+    let frames = frame_rects(image.width(), image.height(), grid)
+        .into_iter()
+        .enumerate()
+        .map(|(frame_index, cell)| {
+            let frame = AtlasFrame {
+                frame: AtlasRect {
+                    x: cell.x,
+                    y: cell.y,
+                    w: cell.width,
+                    h: cell.height,
+                },
+                rotated: false,
+                trimmed: false,
+                sprite_source_size: AtlasRect {
+                    x: 0,
+                    y: 0,
+                    w: cell.width,
+                    h: cell.height,
+                },
+                source_size: AtlasSize {
+                    w: cell.width,
+                    h: cell.height,
+                },
+            };
+            (format!("{name}_{frame_index:03}.png"), frame)
+        })
+        .collect();
+    let atlas = Atlas {
+        frames,
+        meta: AtlasMeta {
+            app: "sprite-designer".to_string(),
+            version: "1.0".to_string(),
+            image: image_name.clone(),
+            format: "RGBA8888".to_string(),
+            size: AtlasSize {
+                w: image.width(),
+                h: image.height(),
+            },
+            scale: "1".to_string(),
+        },
+    };
+
+    let image_path = destination_dir.join(&image_name);
+    let png_bytes = encode_png_optimized(image.as_raw(), image.width(), image.height())?;
+    fs::write(&image_path, png_bytes)?;
+    let atlas_path = destination_dir.join(format!("{name}.json"));
+    write_json(&atlas_path, &atlas)?;
+
+    Ok(AtlasExport {
+        image_path: image_path.to_string_lossy().to_string(),
+        atlas_path: atlas_path.to_string_lossy().to_string(),
+    })
+}
+
+/// Each grid cell in play order, alongside its rectangle in the sheet.
+fn sheet_frames(image: &RgbaImage, grid: FrameGrid) -> Vec<(Rect, RgbaImage)> {
+    frame_rects(image.width(), image.height(), grid)
+        .into_iter()
+        .map(|cell| {
+            let frame = imageops::crop_imm(image, cell.x, cell.y, cell.width, cell.height);
+            (cell, frame.to_image())
+        })
+        .collect()
+}
+
+/// The opaque content box of each frame, offset into sheet coordinates. A fully
+/// transparent frame reports a zero-sized rect at its cell's origin.
+pub fn frame_bounds(source_image_path: &Path, grid: FrameGrid) -> AppResult<Vec<Rect>> {
+    let image = load_rgba_image(source_image_path)?;
+    Ok(sheet_frames(&image, grid)
+        .into_iter()
+        .map(|(cell, frame)| {
+            let (_, content) = trim_transparent(&frame);
+            Rect {
+                x: cell.x + content.x,
+                y: cell.y + content.y,
+                ..content
+            }
+        })
+        .collect())
+}
+
+/// Crops to the bounding box of pixels with non-zero alpha and returns that box. A fully
+/// transparent image comes back as a 1x1 transparent image with a zero-sized rect.
+pub fn trim_transparent(image: &RgbaImage) -> (RgbaImage, Rect) {
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel[3] == 0 {
+            continue;
+        }
+        bounds = Some(match bounds {
+            Some((min_x, min_y, max_x, max_y)) => {
+                (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+            }
+            None => (x, y, x, y),
+        });
     }
 
-    let bytes = fs::read(path)?;
-    let mime = match path.extension().and_then(|ext| ext.to_str()) {
-        Some("jpg") | Some("jpeg") => "image/jpeg",
-        Some("webp") => "image/webp",
-        _ => "image/png",
+    let Some((min_x, min_y, max_x, max_y)) = bounds else {
+        return (RgbaImage::new(1, 1), Rect::default());
     };
 
-    Ok(format!("data:{mime};base64,{}", STANDARD.encode(bytes)))
+    let rect = Rect {
+        x: min_x,
+        y: min_y,
+        width: max_x - min_x + 1,
+        height: max_y - min_y + 1,
+    };
+    let trimmed = imageops::crop_imm(image, rect.x, rect.y, rect.width, rect.height).to_image();
+    (trimmed, rect)
 }
 
-pub fn export_image_to_path(
-    source_image_path: &Path,
-    destination_path: &Path,
-    remove_chromakey_background: bool,
-) -> AppResult<String> {
-    if !source_image_path.exists() {
-        return Err(AppError::msg(format!(
-            "source image path not found: {}",
-            source_image_path.display()
-        )));
-    }
+/// Cell rectangles listed in the grid's play order.
+pub fn frame_rects(width: u32, height: u32, grid: FrameGrid) -> Vec<Rect> {
+    let cells = grid_cell_rects(width, height, grid.rows, grid.cols);
+    grid.order
+        .play_order(grid.rows, grid.cols)
+        .into_iter()
+        .filter_map(|index| cells.get(index).copied())
+        .collect()
+}
 
-    let mut output_path = destination_path.to_path_buf();
-    if remove_chromakey_background {
-        output_path.set_extension("png");
-    } else if output_path.extension().is_none() {
-        output_path.set_extension("png");
+/// Cell rectangles for a rows x cols grid in row-major order. Uses the same integer-division
+/// spans as the chromakey cell seeding so frames line up with the keyed regions.
+pub fn grid_cell_rects(width: u32, height: u32, rows: u32, cols: u32) -> Vec<Rect> {
+    let mut cells = Vec::new();
+    if rows == 0 || cols == 0 {
+        return cells;
     }
 
-    if let Some(parent) = output_path.parent() {
-        fs::create_dir_all(parent)?;
+    for row in 0..rows {
+        let y_start = (row * height) / rows;
+        let y_end = ((row + 1) * height) / rows;
+        for col in 0..cols {
+            let x_start = (col * width) / cols;
+            let x_end = ((col + 1) * width) / cols;
+            cells.push(Rect {
+                x: x_start,
+                y: y_start,
+                width: x_end - x_start,
+                height: y_end - y_start,
+            });
+        }
     }
 
-    if remove_chromakey_background {
-        let source_bytes = fs::read(source_image_path)?;
-        let mut image = image::load_from_memory(&source_bytes)?.into_rgba8();
-        apply_export_chromakey_transparency(&mut image);
-        let png_bytes = encode_png_optimized(image.as_raw(), image.width(), image.height())?;
-        fs::write(&output_path, png_bytes)?;
-    } else {
-        fs::copy(source_image_path, &output_path)?;
-    }
+    cells
+}
 
-    Ok(output_path.to_string_lossy().to_string())
+fn rename_replacing(from: &Path, to: &Path) -> AppResult<()> {
+    if cfg!(windows) && to.exists() {
+        fs::remove_file(to)?;
+    }
+    fs::rename(from, to)?;
+    Ok(())
 }
 
 fn ensure_project_dirs(app: &AppHandle, project_id: &str) -> AppResult<()> {
@@ -321,7 +2866,113 @@ fn normalize_project_name(name: Option<String>) -> String {
     }
 }
 
-fn apply_chromakey_transparency(image: &mut RgbaImage, sprite_grid: Option<(u32, u32)>) {
+/// The background color being keyed out plus the tolerances used to match it. Channels at
+/// or above 128 in the key are its "dominant" channels; a pixel looks like the key when
+/// those channels lead the others.
+#[derive(Debug, Clone, Copy)]
+struct Keyer {
+    rgb: [u8; 3],
+    dominant: [bool; 3],
+    settings: ChromakeySettings,
+    /// The two gray levels of a checkerboard background, darker first.
+    checker_levels: [u8; 2],
+}
+
+impl Keyer {
+    fn new(rgb: [u8; 3], settings: ChromakeySettings) -> Self {
+        Self {
+            rgb,
+            dominant: rgb.map(|channel| channel >= 128),
+            settings,
+            checker_levels: [0; 2],
+        }
+    }
+
+    /// Keys a checkerboard of the two gray `levels`. Distances used for feathering are taken
+    /// from the lighter one.
+    fn checkerboard(levels: [u8; 2], settings: ChromakeySettings) -> Self {
+        Self {
+            checker_levels: [levels[0].min(levels[1]), levels[0].max(levels[1])],
+            ..Self::new([levels[0].max(levels[1]); 3], settings)
+        }
+    }
+
+    /// Whether the key is a saturated color, which is what the strength/lead heuristics and
+    /// despill assume. White, gray and black keys are only flood-filled from the borders.
+    fn is_color(&self) -> bool {
+        let low = self.rgb.iter().min().copied().unwrap_or(0);
+        let high = self.rgb.iter().max().copied().unwrap_or(0);
+        self.settings.background == KeyBackground::Color && high - low >= KEY_MIN_SATURATION
+    }
+
+    /// How far the dominant channels lead the rest (for green: `g - max(r, b)`).
+    fn lead(&self, r: u8, g: u8, b: u8) -> u8 {
+        self.strength(r, g, b)
+            .saturating_sub(self.max_other(r, g, b))
+    }
+
+    /// The weakest dominant channel (for green: `g`).
+    fn strength(&self, r: u8, g: u8, b: u8) -> u8 {
+        [r, g, b]
+            .iter()
+            .zip(self.dominant)
+            .filter(|(_, dominant)| *dominant)
+            .map(|(value, _)| *value)
+            .min()
+            .unwrap_or(255)
+    }
+
+    fn max_other(&self, r: u8, g: u8, b: u8) -> u8 {
+        [r, g, b]
+            .iter()
+            .zip(self.dominant)
+            .filter(|(_, dominant)| !*dominant)
+            .map(|(value, _)| *value)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn distance_sq(&self, r: u8, g: u8, b: u8) -> u32 {
+        let dr = r as i32 - self.rgb[0] as i32;
+        let dg = g as i32 - self.rgb[1] as i32;
+        let db = b as i32 - self.rgb[2] as i32;
+
+        (dr * dr + dg * dg + db * db) as u32
+    }
+}
+
+/// Keys with a smaller channel spread than this are white, gray or black. On those every
+/// light (or dark) subject pixel looks like strong key, so the color-only passes are skipped.
+const KEY_MIN_SATURATION: u8 = 48;
+
+/// Keys whatever background the border shows. A checkerboard or near-white border gets its
+/// dedicated key instead of the saturated-color heuristics, which would eat light pixels
+/// anywhere in the subject.
+fn auto_detected_keyer(image: &RgbaImage, settings: ChromakeySettings) -> Option<Keyer> {
+    if let Some(levels) = detect_checkerboard(image) {
+        let settings = ChromakeySettings {
+            background: KeyBackground::Checkerboard,
+            ..settings
+        };
+        return Some(Keyer::checkerboard(levels, settings));
+    }
+
+    let [r, g, b] = detect_background_color(image)?;
+    if matches_white_key(r, g, b, ChromaMatchMode::Expand) {
+        let settings = ChromakeySettings {
+            background: KeyBackground::White,
+            ..settings
+        };
+        return Some(Keyer::new([255; 3], settings));
+    }
+    Some(Keyer::new([r, g, b], settings))
+}
+
+fn apply_chromakey_transparency(
+    image: &mut RgbaImage,
+    sprite_grid: Option<(u32, u32)>,
+    key: Keyer,
+) {
     let (width, height) = image.dimensions();
     if width == 0 || height == 0 {
         return;
@@ -332,11 +2983,13 @@ fn apply_chromakey_transparency(image: &mut RgbaImage, sprite_grid: Option<(u32,
 
     let seeded = sprite_grid
         .filter(|(rows, cols)| *rows > 0 && *cols > 0)
-        .map(|(rows, cols)| enqueue_chromakey_cell_borders(rows, cols, image, &mut visited, &mut queue))
+        .map(|(rows, cols)| {
+            enqueue_chromakey_cell_borders(rows, cols, image, &mut visited, &mut queue, key)
+        })
         .unwrap_or(false);
 
     if !seeded {
-        enqueue_chromakey_borders(image, &mut visited, &mut queue);
+        enqueue_chromakey_borders(image, &mut visited, &mut queue, key);
     }
 
     while let Some((x, y)) = queue.pop_front() {
@@ -348,8 +3001,18 @@ fn apply_chromakey_transparency(image: &mut RgbaImage, sprite_grid: Option<(u32,
             (x, y.wrapping_sub(1), y > 0),
             (x, y + 1, y + 1 < height),
         ];
+        let diagonals = [
+            (x.wrapping_sub(1), y.wrapping_sub(1), x > 0 && y > 0),
+            (x + 1, y.wrapping_sub(1), x + 1 < width && y > 0),
+            (x.wrapping_sub(1), y + 1, x > 0 && y + 1 < height),
+            (x + 1, y + 1, x + 1 < width && y + 1 < height),
+        ];
+        let diagonal_count = if key.settings.eight_connected { 4 } else { 0 };
 
-        for (nx, ny, in_bounds) in neighbors {
+        for (nx, ny, in_bounds) in neighbors
+            .into_iter()
+            .chain(diagonals.into_iter().take(diagonal_count))
+        {
             if in_bounds {
                 enqueue_if_chromakey(
                     nx,
@@ -358,47 +3021,187 @@ fn apply_chromakey_transparency(image: &mut RgbaImage, sprite_grid: Option<(u32,
                     &mut visited,
                     &mut queue,
                     ChromaMatchMode::Expand,
+                    key,
                 );
             }
         }
     }
 
-    clear_strong_chromakey_anywhere(image);
-    clear_chromakey_fringe(image, 2);
+    // Clearing a white or gray key anywhere would punch holes in highlights and metal.
+    if key.is_color() && key.settings.mode == ChromakeyMode::Aggressive {
+        clear_strong_chromakey_anywhere(image, key);
+        clear_chromakey_fringe(image, key.settings.fringe_passes, key);
+    }
+    if key.settings.feather_radius > 0 {
+        feather_chroma_edges(image, key.settings.feather_radius, key);
+    }
+    if key.is_color() && key.settings.despill_strength > 0.0 {
+        suppress_chroma_spill(image, key.settings.despill_strength, key);
+    }
+    if key.settings.despeckle_min_alpha > 0 {
+        despeckle_alpha(
+            image,
+            key.settings.despeckle_min_alpha,
+            key.settings.despeckle_min_cluster,
+        );
+    }
+}
+
+/// Clears faint specks keying leaves behind. Pixels with alpha in `1..min_alpha` are grouped
+/// into 8-connected clusters; a cluster is cleared when it doesn't touch any pixel at or above
+/// `min_alpha` and has fewer than `min_cluster` pixels (a lone pixel always goes). Faint
+/// pixels attached to the subject, like a feathered edge or a thin antenna, are kept.
+pub fn despeckle_alpha(image: &mut RgbaImage, min_alpha: u8, min_cluster: usize) {
+    let (width, height) = image.dimensions();
+    let is_faint = |alpha: u8| alpha > 0 && alpha < min_alpha;
+    let mut visited = vec![false; (width * height) as usize];
+    let mut cluster = Vec::new();
+    let mut stack = Vec::new();
+
+    for start_y in 0..height {
+        for start_x in 0..width {
+            let start = (start_y * width + start_x) as usize;
+            if visited[start] || !is_faint(image.get_pixel(start_x, start_y)[3]) {
+                continue;
+            }
+
+            cluster.clear();
+            let mut attached = false;
+            visited[start] = true;
+            stack.push((start_x, start_y));
+            while let Some((x, y)) = stack.pop() {
+                cluster.push((x, y));
+                for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                    for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                        let alpha = image.get_pixel(nx, ny)[3];
+                        if alpha >= min_alpha {
+                            attached = true;
+                            continue;
+                        }
+                        let index = (ny * width + nx) as usize;
+                        if is_faint(alpha) && !visited[index] {
+                            visited[index] = true;
+                            stack.push((nx, ny));
+                        }
+                    }
+                }
+            }
+
+            if !attached && cluster.len() < min_cluster.max(2) {
+                for &(x, y) in &cluster {
+                    image.put_pixel(x, y, image::Rgba([0, 0, 0, 0]));
+                }
+            }
+        }
+    }
+}
+
+const SPILL_MIN_LEAD: u8 = 8;
+
+/// Pulls the key's dominant channels down toward the strongest other channel on opaque
+/// pixels where they noticeably lead, e.g. `g` toward `max(r, b)` for a green key.
+fn suppress_chroma_spill(image: &mut RgbaImage, strength: f32, key: Keyer) {
+    let strength = strength.clamp(0.0, 1.0);
+    for pixel in image.pixels_mut() {
+        if pixel[3] == 0 {
+            continue;
+        }
+
+        despill_pixel(pixel, strength, key);
+    }
+}
+
+fn despill_pixel(pixel: &mut image::Rgba<u8>, strength: f32, key: Keyer) {
+    let (r, g, b) = (pixel[0], pixel[1], pixel[2]);
+    if !key.is_color() || key.lead(r, g, b) < SPILL_MIN_LEAD {
+        return;
+    }
+
+    let ceiling = key.max_other(r, g, b) as f32;
+    for channel in 0..3 {
+        if key.dominant[channel] {
+            let value = pixel[channel] as f32;
+            pixel[channel] = (value - (value - ceiling) * strength).round() as u8;
+        }
+    }
+}
+
+/// Squared key distance at and beyond which an edge pixel keeps its full alpha.
+const FEATHER_OPAQUE_DISTANCE_SQ: u32 = 55_000;
+
+/// Softens the keyed edge: opaque pixels within `radius` of a transparent one get partial
+/// alpha based on how close they are to the key color, with pixels deeper inside the subject
+/// feathered less. Feathered pixels are fully despilled so the blend doesn't show a key tint.
+fn feather_chroma_edges(image: &mut RgbaImage, radius: u32, key: Keyer) {
+    let (width, height) = image.dimensions();
+    let source = image.clone();
+    let max_distance = (FEATHER_OPAQUE_DISTANCE_SQ as f32).sqrt();
+
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = source.get_pixel(x, y).0;
+            if pixel[3] == 0 {
+                continue;
+            }
+
+            let Some(edge_distance) = distance_to_transparent(&source, x, y, radius) else {
+                continue;
+            };
+
+            let chroma = (key.distance_sq(pixel[0], pixel[1], pixel[2]) as f32).sqrt();
+            let chroma_factor = (chroma / max_distance).min(1.0);
+            let depth_factor = (edge_distance - 1) as f32 / radius as f32;
+            let factor = chroma_factor.max(depth_factor);
+            if factor >= 1.0 {
+                continue;
+            }
+
+            let output = image.get_pixel_mut(x, y);
+            output[3] = (pixel[3] as f32 * factor).round() as u8;
+            despill_pixel(output, 1.0, key);
+        }
+    }
+}
+
+/// Chebyshev distance from (x, y) to the nearest fully transparent pixel, if one lies within
+/// `radius`.
+fn distance_to_transparent(image: &RgbaImage, x: u32, y: u32, radius: u32) -> Option<u32> {
+    let (width, height) = image.dimensions();
+    (1..=radius).find(|distance| {
+        let x_min = x.saturating_sub(*distance);
+        let y_min = y.saturating_sub(*distance);
+        let x_max = (x + distance).min(width - 1);
+        let y_max = (y + distance).min(height - 1);
+
+        (y_min..=y_max).any(|ny| {
+            (x_min..=x_max).any(|nx| {
+                let on_ring = nx.abs_diff(x) == *distance || ny.abs_diff(y) == *distance;
+                on_ring && image.get_pixel(nx, ny).0[3] == 0
+            })
+        })
+    })
 }
 
 fn enqueue_chromakey_borders(
     image: &RgbaImage,
     visited: &mut [bool],
     queue: &mut VecDeque<(u32, u32)>,
+    key: Keyer,
 ) {
     let (width, height) = image.dimensions();
+    let seed = ChromaMatchMode::Seed;
 
     for x in 0..width {
-        let _ = enqueue_if_chromakey(x, 0, image, visited, queue, ChromaMatchMode::Seed);
+        let _ = enqueue_if_chromakey(x, 0, image, visited, queue, seed, key);
         if height > 1 {
-            let _ = enqueue_if_chromakey(
-                x,
-                height - 1,
-                image,
-                visited,
-                queue,
-                ChromaMatchMode::Seed,
-            );
+            let _ = enqueue_if_chromakey(x, height - 1, image, visited, queue, seed, key);
         }
     }
 
     for y in 0..height {
-        let _ = enqueue_if_chromakey(0, y, image, visited, queue, ChromaMatchMode::Seed);
+        let _ = enqueue_if_chromakey(0, y, image, visited, queue, seed, key);
         if width > 1 {
-            let _ = enqueue_if_chromakey(
-                width - 1,
-                y,
-                image,
-                visited,
-                queue,
-                ChromaMatchMode::Seed,
-            );
+            let _ = enqueue_if_chromakey(width - 1, y, image, visited, queue, seed, key);
         }
     }
 }
@@ -409,10 +3212,23 @@ fn enqueue_chromakey_cell_borders(
     image: &RgbaImage,
     visited: &mut [bool],
     queue: &mut VecDeque<(u32, u32)>,
+    key: Keyer,
 ) -> bool {
     let (width, height) = image.dimensions();
     let mut seeded = false;
 
+    for (x, y) in cell_border_points(width, height, rows, cols) {
+        seeded |= enqueue_if_chromakey(x, y, image, visited, queue, ChromaMatchMode::Seed, key);
+    }
+
+    seeded
+}
+
+/// Points along the border of every grid cell, inset one pixel so seams between cells and
+/// the image edge don't bias sampling.
+fn cell_border_points(width: u32, height: u32, rows: u32, cols: u32) -> Vec<(u32, u32)> {
+    let mut points = Vec::new();
+
     for row in 0..rows {
         let y_start = (row * height) / rows;
         let y_end = (((row + 1) * height) / rows).saturating_sub(1);
@@ -430,45 +3246,100 @@ fn enqueue_chromakey_cell_borders(
             let (left, right) = inner_span(x_start, x_end);
 
             for x in left..=right {
-                seeded |= enqueue_if_chromakey(
-                    x,
-                    top,
-                    image,
-                    visited,
-                    queue,
-                    ChromaMatchMode::Seed,
-                );
-                seeded |= enqueue_if_chromakey(
-                    x,
-                    bottom,
-                    image,
-                    visited,
-                    queue,
-                    ChromaMatchMode::Seed,
-                );
+                points.push((x, top));
+                points.push((x, bottom));
             }
             for y in top..=bottom {
-                seeded |= enqueue_if_chromakey(
-                    left,
-                    y,
-                    image,
-                    visited,
-                    queue,
-                    ChromaMatchMode::Seed,
-                );
-                seeded |= enqueue_if_chromakey(
-                    right,
-                    y,
-                    image,
-                    visited,
-                    queue,
-                    ChromaMatchMode::Seed,
-                );
+                points.push((left, y));
+                points.push((right, y));
             }
         }
     }
 
-    seeded
+    points
+}
+
+/// Share of border samples that must fall in one color bucket before it counts as the
+/// background.
+const BACKGROUND_MIN_SHARE: f64 = 0.5;
+
+/// Finds the most common near-constant color along the image border, averaged within its
+/// bucket. Returns `None` when the border is too varied to trust as a background.
+pub fn detect_background_color(image: &RgbaImage) -> Option<[u8; 3]> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let points = cell_border_points(width, height, 1, 1);
+    let mut buckets: HashMap<[u8; 3], (usize, [u64; 3])> = HashMap::new();
+    for (x, y) in &points {
+        let pixel = image.get_pixel(*x, *y).0;
+        let bucket = [pixel[0] >> 4, pixel[1] >> 4, pixel[2] >> 4];
+        let entry = buckets.entry(bucket).or_insert((0, [0; 3]));
+        entry.0 += 1;
+        for (sum, value) in entry.1.iter_mut().zip(pixel) {
+            *sum += value as u64;
+        }
+    }
+
+    let (count, sums) = buckets.into_values().max_by_key(|(count, _)| *count)?;
+    if (count as f64) < points.len() as f64 * BACKGROUND_MIN_SHARE {
+        return None;
+    }
+
+    Some(sums.map(|sum| (sum / count as u64) as u8))
+}
+
+/// Border samples must be this gray (max minus min channel) to count toward a checkerboard.
+const CHECKER_MAX_SPREAD: u8 = 10;
+/// Each of the two checkerboard grays must cover this share of the border samples.
+const CHECKER_MIN_SHARE: f64 = 0.2;
+/// The two grays must differ by at least this much, or it's just a flat gray background.
+const CHECKER_MIN_CONTRAST: u8 = 12;
+
+/// Finds the two alternating grays of a transparency checkerboard along the image border,
+/// darker first. Returns `None` unless two distinct gray levels each cover a fair share of
+/// the border and together make up most of it.
+pub fn detect_checkerboard(image: &RgbaImage) -> Option<[u8; 2]> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let points = cell_border_points(width, height, 1, 1);
+    let mut buckets: HashMap<u8, (usize, u64)> = HashMap::new();
+    for (x, y) in &points {
+        let pixel = image.get_pixel(*x, *y).0;
+        let low = pixel[0].min(pixel[1]).min(pixel[2]);
+        let high = pixel[0].max(pixel[1]).max(pixel[2]);
+        if high - low > CHECKER_MAX_SPREAD {
+            continue;
+        }
+        let level = (pixel[0] as u64 + pixel[1] as u64 + pixel[2] as u64) / 3;
+        let entry = buckets.entry(level as u8 >> 3).or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 += level;
+    }
+
+    let mut ranked = buckets.into_values().collect::<Vec<_>>();
+    ranked.sort_by_key(|(count, _)| std::cmp::Reverse(*count));
+    let [(first_count, first_sum), (second_count, second_sum)] = ranked.get(..2)? else {
+        return None;
+    };
+    let total = points.len() as f64;
+    if (*second_count as f64) < total * CHECKER_MIN_SHARE
+        || ((first_count + second_count) as f64) < total * BACKGROUND_MIN_SHARE
+    {
+        return None;
+    }
+
+    let first = (first_sum / *first_count as u64) as u8;
+    let second = (second_sum / *second_count as u64) as u8;
+    if first.abs_diff(second) < CHECKER_MIN_CONTRAST {
+        return None;
+    }
+    Some([first.min(second), first.max(second)])
 }
 
 fn inner_span(start: u32, end: u32) -> (u32, u32) {
@@ -486,6 +3357,7 @@ fn enqueue_if_chromakey(
     visited: &mut [bool],
     queue: &mut VecDeque<(u32, u32)>,
     mode: ChromaMatchMode,
+    key: Keyer,
 ) -> bool {
     let width = image.width();
     let index = (y * width + x) as usize;
@@ -494,7 +3366,7 @@ fn enqueue_if_chromakey(
     }
 
     let pixel = image.get_pixel(x, y).0;
-    if matches_chromakey(pixel[0], pixel[1], pixel[2], mode) {
+    if matches_chromakey(pixel[0], pixel[1], pixel[2], mode, key) {
         visited[index] = true;
         queue.push_back((x, y));
         return true;
@@ -509,58 +3381,85 @@ enum ChromaMatchMode {
     Expand,
 }
 
-fn matches_chromakey(r: u8, g: u8, b: u8, mode: ChromaMatchMode) -> bool {
-    let max_rb = r.max(b);
-    let green_lead = g.saturating_sub(max_rb);
-    let dist_sq = chroma_green_distance_sq(r, g, b);
+fn matches_chromakey(r: u8, g: u8, b: u8, mode: ChromaMatchMode, key: Keyer) -> bool {
+    match key.settings.background {
+        KeyBackground::Color => {}
+        KeyBackground::White => return matches_white_key(r, g, b, mode),
+        KeyBackground::Checkerboard => return matches_checkerboard_key(r, g, b, mode, key),
+    }
+
+    let strength = key.strength(r, g, b);
+    let lead = key.lead(r, g, b);
+    let dist_sq = key.distance_sq(r, g, b);
 
     match mode {
         ChromaMatchMode::Seed => {
-            if g < 80 || green_lead < 18 {
+            if strength < 80 || lead < 18 {
                 return false;
             }
-            dist_sq <= 30_000
+            dist_sq <= key.settings.seed_tolerance
         }
         ChromaMatchMode::Expand => {
-            if g < 40 || green_lead < 6 {
+            if strength < 40 || lead < 6 {
                 return false;
             }
-            dist_sq <= 45_000
+            dist_sq <= key.settings.expand_tolerance
         }
     }
 }
 
-fn chroma_green_distance_sq(r: u8, g: u8, b: u8) -> u32 {
-    let dr = r as i32;
-    let dg = 255_i32 - g as i32;
-    let db = b as i32;
+/// Bright and nearly colorless. Expansion accepts slightly dimmer, slightly tinted pixels so
+/// the fill reaches through JPEG noise and soft shadows near the edge.
+fn matches_white_key(r: u8, g: u8, b: u8, mode: ChromaMatchMode) -> bool {
+    let (min_level, max_spread) = match mode {
+        ChromaMatchMode::Seed => (235, 12),
+        ChromaMatchMode::Expand => (215, 20),
+    };
+    let low = r.min(g).min(b);
+    let high = r.max(g).max(b);
+
+    low >= min_level && high - low <= max_spread
+}
+
+/// Gray and close to either checkerboard level.
+fn matches_checkerboard_key(r: u8, g: u8, b: u8, mode: ChromaMatchMode, key: Keyer) -> bool {
+    let (max_level_delta, max_spread) = match mode {
+        ChromaMatchMode::Seed => (10, 10),
+        ChromaMatchMode::Expand => (18, 16),
+    };
+    let low = r.min(g).min(b);
+    let high = r.max(g).max(b);
+    if high - low > max_spread {
+        return false;
+    }
 
-    (dr * dr + dg * dg + db * db) as u32
+    let level = ((r as u32 + g as u32 + b as u32) / 3) as u8;
+    key.checker_levels
+        .iter()
+        .any(|checker| level.abs_diff(*checker) <= max_level_delta)
 }
 
-fn clear_strong_chromakey_anywhere(image: &mut RgbaImage) {
+fn clear_strong_chromakey_anywhere(image: &mut RgbaImage, key: Keyer) {
     for pixel in image.pixels_mut() {
         if pixel[3] == 0 {
             continue;
         }
 
-        if matches_chromakey_global_strong(pixel[0], pixel[1], pixel[2]) {
+        if matches_chromakey_global_strong(pixel[0], pixel[1], pixel[2], key) {
             *pixel = image::Rgba([0, 0, 0, 0]);
         }
     }
 }
 
-fn matches_chromakey_global_strong(r: u8, g: u8, b: u8) -> bool {
-    let max_rb = r.max(b);
-    let green_lead = g.saturating_sub(max_rb);
-    if g < 95 || green_lead < 20 {
+fn matches_chromakey_global_strong(r: u8, g: u8, b: u8, key: Keyer) -> bool {
+    if key.strength(r, g, b) < 95 || key.lead(r, g, b) < 20 {
         return false;
     }
 
-    chroma_green_distance_sq(r, g, b) <= 36_000
+    key.distance_sq(r, g, b) <= key.settings.global_strong_tolerance
 }
 
-fn clear_chromakey_fringe(image: &mut RgbaImage, passes: usize) {
+fn clear_chromakey_fringe(image: &mut RgbaImage, passes: usize, key: Keyer) {
     let (width, height) = image.dimensions();
     for _ in 0..passes {
         let mut to_clear = Vec::new();
@@ -572,7 +3471,7 @@ fn clear_chromakey_fringe(image: &mut RgbaImage, passes: usize) {
                     continue;
                 }
 
-                if !matches_chromakey_fringe(pixel[0], pixel[1], pixel[2]) {
+                if !matches_chromakey_fringe(pixel[0], pixel[1], pixel[2], key) {
                     continue;
                 }
 
@@ -592,7 +3491,7 @@ fn clear_chromakey_fringe(image: &mut RgbaImage, passes: usize) {
     }
 }
 
-fn apply_export_chromakey_transparency(image: &mut RgbaImage) {
+fn apply_export_chromakey_transparency(image: &mut RgbaImage, key: Keyer) {
     let (width, height) = image.dimensions();
     if width == 0 || height == 0 {
         return;
@@ -602,7 +3501,7 @@ fn apply_export_chromakey_transparency(image: &mut RgbaImage) {
     let mut queue = VecDeque::new();
 
     for x in 0..width {
-        let _ = enqueue_if_export_chromakey(x, 0, image, &mut visited, &mut queue, true);
+        let _ = enqueue_if_export_chromakey(x, 0, image, &mut visited, &mut queue, true, key);
         if height > 1 {
             let _ = enqueue_if_export_chromakey(
                 x,
@@ -611,12 +3510,13 @@ fn apply_export_chromakey_transparency(image: &mut RgbaImage) {
                 &mut visited,
                 &mut queue,
                 true,
+                key,
             );
         }
     }
 
     for y in 0..height {
-        let _ = enqueue_if_export_chromakey(0, y, image, &mut visited, &mut queue, true);
+        let _ = enqueue_if_export_chromakey(0, y, image, &mut visited, &mut queue, true, key);
         if width > 1 {
             let _ = enqueue_if_export_chromakey(
                 width - 1,
@@ -625,6 +3525,7 @@ fn apply_export_chromakey_transparency(image: &mut RgbaImage) {
                 &mut visited,
                 &mut queue,
                 true,
+                key,
             );
         }
     }
@@ -648,12 +3549,13 @@ fn apply_export_chromakey_transparency(image: &mut RgbaImage) {
                     &mut visited,
                     &mut queue,
                     false,
+                    key,
                 );
             }
         }
     }
 
-    clear_export_chromakey_fringe(image, 2);
+    clear_export_chromakey_fringe(image, 2, key);
 }
 
 fn enqueue_if_export_chromakey(
@@ -663,6 +3565,7 @@ fn enqueue_if_export_chromakey(
     visited: &mut [bool],
     queue: &mut VecDeque<(u32, u32)>,
     seed: bool,
+    key: Keyer,
 ) -> bool {
     let width = image.width();
     let index = (y * width + x) as usize;
@@ -671,7 +3574,7 @@ fn enqueue_if_export_chromakey(
     }
 
     let pixel = image.get_pixel(x, y).0;
-    if matches_export_chromakey(pixel[0], pixel[1], pixel[2], seed) {
+    if matches_export_chromakey(pixel[0], pixel[1], pixel[2], seed, key) {
         visited[index] = true;
         queue.push_back((x, y));
         return true;
@@ -680,25 +3583,25 @@ fn enqueue_if_export_chromakey(
     false
 }
 
-fn matches_export_chromakey(r: u8, g: u8, b: u8, seed: bool) -> bool {
-    let max_rb = r.max(b);
-    let green_lead = g.saturating_sub(max_rb);
-    let dist_sq = chroma_green_distance_sq(r, g, b);
+fn matches_export_chromakey(r: u8, g: u8, b: u8, seed: bool, key: Keyer) -> bool {
+    let strength = key.strength(r, g, b);
+    let lead = key.lead(r, g, b);
+    let dist_sq = key.distance_sq(r, g, b);
 
     if seed {
-        if g < 80 || green_lead < 16 {
+        if strength < 80 || lead < 16 {
             return false;
         }
         return dist_sq <= 34_000;
     }
 
-    if g < 35 || green_lead < 4 {
+    if strength < 35 || lead < 4 {
         return false;
     }
     dist_sq <= 50_000
 }
 
-fn clear_export_chromakey_fringe(image: &mut RgbaImage, passes: usize) {
+fn clear_export_chromakey_fringe(image: &mut RgbaImage, passes: usize, key: Keyer) {
     let (width, height) = image.dimensions();
     for _ in 0..passes {
         let mut to_clear = Vec::new();
@@ -710,7 +3613,7 @@ fn clear_export_chromakey_fringe(image: &mut RgbaImage, passes: usize) {
                     continue;
                 }
 
-                if !matches_export_chromakey(pixel[0], pixel[1], pixel[2], false) {
+                if !matches_export_chromakey(pixel[0], pixel[1], pixel[2], false, key) {
                     continue;
                 }
 
@@ -730,14 +3633,12 @@ fn clear_export_chromakey_fringe(image: &mut RgbaImage, passes: usize) {
     }
 }
 
-fn matches_chromakey_fringe(r: u8, g: u8, b: u8) -> bool {
-    let max_rb = r.max(b);
-    let green_lead = g.saturating_sub(max_rb);
-    if g < 35 || green_lead < 2 {
+fn matches_chromakey_fringe(r: u8, g: u8, b: u8, key: Keyer) -> bool {
+    if key.strength(r, g, b) < 35 || key.lead(r, g, b) < 2 {
         return false;
     }
 
-    chroma_green_distance_sq(r, g, b) <= 55_000
+    key.distance_sq(r, g, b) <= 55_000
 }
 
 fn has_transparent_neighbor(image: &RgbaImage, x: u32, y: u32, width: u32, height: u32) -> bool {
@@ -781,18 +3682,43 @@ fn encode_png_optimized(rgba: &[u8], width: u32, height: u32) -> AppResult<Vec<u
         .map_err(|error| AppError::msg(format!("failed to optimize png: {error}")))
 }
 
-fn read_json<T: DeserializeOwned>(path: &Path) -> AppResult<T> {
+pub(crate) fn read_json<T: DeserializeOwned>(path: &Path) -> AppResult<T> {
     let contents = fs::read_to_string(path)?;
     let value = serde_json::from_str::<T>(&contents)?;
     Ok(value)
 }
 
-fn write_json<T: Serialize>(path: &Path, value: &T) -> AppResult<()> {
+pub(crate) fn write_json<T: Serialize>(path: &Path, value: &T) -> AppResult<()> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
     }
 
     let contents = serde_json::to_string_pretty(value)?;
-    fs::write(path, contents)?;
+    write_file_atomic(path, contents.as_bytes())
+}
+
+/// Writes to a sibling temp file and renames it over `path`, so a crash mid-write leaves
+/// either the old contents or the new ones, never a torn file. Each write gets its own temp
+/// file, so concurrent writers to the same path can't truncate or rename each other's.
+fn write_file_atomic(path: &Path, contents: &[u8]) -> AppResult<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| AppError::msg(format!("invalid file path: {}", path.display())))?;
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        Uuid::new_v4()
+    ));
+
+    let result = fs::File::create(&temp_path).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&temp_path, path)
+    });
+    if let Err(error) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(error.into());
+    }
     Ok(())
 }
@@ -330,10 +330,16 @@ function App() {
   const loadProjects = React.useCallback(async () => {
     setIsLoadingProjects(true);
     try {
-      const summaries = await listProjects();
+      const { projects: summaries, errored } = await listProjects();
       const fullProjects = await Promise.all(
         summaries.map((summary) => getProject(summary.id)),
       );
+      if (errored.length > 0) {
+        console.warn("Skipped unreadable projects", errored);
+        setGenerateError(
+          `Skipped ${errored.length} unreadable project${errored.length === 1 ? "" : "s"}.`,
+        );
+      }
       fullProjects.sort(
         (a, b) =>
           new Date(b.updatedAt).getTime() - new Date(a.updatedAt).getTime(),
@@ -1,23 +1,62 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { Project, ProjectSummary } from "@sprite-designer/shared/types";
-import type { ChildResult, EditRequest, GenerateRequest } from "./types";
+import type { ChildSummary, Project, ProjectList } from "@sprite-designer/shared/types";
+import type {
+  BatchGenerateResult,
+  ChildResult,
+  EditRequest,
+  GenerateRequest,
+  LogEntry,
+} from "./types";
 
-export async function listProjects(): Promise<ProjectSummary[]> {
-  return invoke<ProjectSummary[]>("list_projects");
+export async function listProjects(offset?: number, limit?: number): Promise<ProjectList> {
+  return invoke<ProjectList>("list_projects", { offset, limit });
 }
 
 export async function getProject(projectId: string): Promise<Project> {
   return invoke<Project>("get_project", { projectId });
 }
 
+export async function listChildrenSummary(projectId: string): Promise<ChildSummary[]> {
+  return invoke<ChildSummary[]>("list_children_summary", { projectId });
+}
+
+export async function reorderChildren(projectId: string, orderedIds: string[]): Promise<string[]> {
+  return invoke<string[]>("reorder_children", { projectId, orderedIds });
+}
+
+export async function buildContactSheet(projectId: string, columns?: number): Promise<string> {
+  return invoke<string>("build_contact_sheet", { projectId, columns });
+}
+
+export async function getRecentLogs(limit?: number): Promise<LogEntry[]> {
+  return invoke<LogEntry[]>("get_recent_logs", { limit });
+}
+
 export async function generateImage(req: GenerateRequest): Promise<ChildResult> {
   return invoke<ChildResult>("generate_image", { req });
 }
 
+export async function batchGenerate(
+  projectId: string,
+  requests: GenerateRequest[],
+  concurrency: number,
+): Promise<BatchGenerateResult[]> {
+  return invoke<BatchGenerateResult[]>("batch_generate", { projectId, requests, concurrency });
+}
+
 export async function editImage(req: EditRequest): Promise<ChildResult> {
   return invoke<ChildResult>("edit_image", { req });
 }
 
+export async function cropImage(
+  projectId: string,
+  childId: string,
+  rect: { x: number; y: number; width: number; height: number },
+  imageIndex?: number,
+): Promise<ChildResult> {
+  return invoke<ChildResult>("crop_image", { projectId, childId, imageIndex, ...rect });
+}
+
 export async function exportImageToPath(
   sourceImagePath: string,
   destinationPath: string,
@@ -34,9 +34,38 @@ export function safeResolution(value?: Resolution): Resolution {
   return value ?? DEFAULT_RESOLUTION;
 }
 
+export type CommandErrorKind =
+  | "missingApiKey"
+  | "network"
+  | "rateLimited"
+  | "refusal"
+  | "noImages"
+  | "validation"
+  | "io"
+  | "decode"
+  | "cancelled"
+  | "other";
+
+export interface CommandError {
+  kind: CommandErrorKind;
+  message: string;
+}
+
+export function isCommandError(error: unknown): error is CommandError {
+  return (
+    typeof error === "object" &&
+    error !== null &&
+    typeof (error as CommandError).kind === "string" &&
+    typeof (error as CommandError).message === "string"
+  );
+}
+
 export function asErrorMessage(error: unknown): string {
   if (error instanceof Error && error.message) {
     return error.message;
   }
+  if (isCommandError(error)) {
+    return error.message;
+  }
   return String(error);
 }
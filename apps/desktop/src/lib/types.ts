@@ -1,4 +1,5 @@
 import type { Child, Project, ProjectSummary, Resolution } from "@sprite-designer/shared/types";
+import type { CommandError } from "./format";
 
 export type AppTab = "generate" | "edit" | "preview" | "export";
 export type SelectionChildId = string | null | "<new>";
@@ -14,17 +15,40 @@ export interface GenerateDraft {
   promptText: string;
   resolution: Resolution;
   imagePriorDataUrl?: string;
+  debug?: boolean;
 }
 
 export interface EditDraft {
   editPrompt: string;
 }
 
+/** The sprite's description names a color close to the chromakey; generation still runs. */
+export interface KeyColorWarning {
+  keyword: string;
+  keyColor: [number, number, number];
+  suggestedColor: [number, number, number];
+  message: string;
+}
+
+/** The keyed sheet's frames don't line up with the requested grid; the child is still kept. */
+export interface GridMismatch {
+  requestedRows: number;
+  requestedCols: number;
+  detectedRows: number;
+  detectedCols: number;
+}
+
 export interface ChildResult {
   project: ProjectSummary;
   child: Child;
+  keyColorWarning?: KeyColorWarning;
+  gridMismatch?: GridMismatch;
+  debugResponse?: unknown;
 }
 
+/** One entry per request passed to `batch_generate`, in the same order. */
+export type BatchGenerateResult = { Ok: ChildResult } | { Err: CommandError };
+
 export interface GenerateRequest {
   projectId?: string;
   name?: string;
@@ -35,8 +59,14 @@ export interface GenerateRequest {
   style?: string;
   cameraAngle?: string;
   promptText?: string;
-  resolution: Resolution;
+  resolution?: Resolution;
   imagePriorDataUrl?: string;
+  /** Extra references (style, pose, ...) sent after `imagePriorDataUrl`. */
+  imagePriorDataUrls?: string[];
+  /** Normal mode only: ask for a seamlessly tiling texture. */
+  tileable?: boolean;
+  /** Sprite mode only: warn via `gridMismatch` when the drawn frames don't match rows x cols. */
+  verifyGrid?: boolean;
 }
 
 export interface EditRequest {
@@ -47,6 +77,16 @@ export interface EditRequest {
   resolution?: Resolution;
   baseImageDataUrl?: string;
   baseImagePath?: string;
+  baseImageIndex?: number;
 }
 
 export interface ProjectRecord extends Project {}
+
+/** One entry from `get_recent_logs`; secrets and image data are redacted on the Rust side. */
+export interface LogEntry {
+  timestamp: string;
+  level: "ERROR" | "WARN" | "INFO" | "DEBUG" | "TRACE";
+  target: string;
+  message: string;
+  fields: Record<string, string>;
+}
@@ -10,6 +10,17 @@ export interface ProjectSummary {
   childCount: number;
 }
 
+export interface ErroredProject {
+  id: string;
+  error: string;
+}
+
+export interface ProjectList {
+  projects: ProjectSummary[];
+  errored: ErroredProject[];
+  total: number;
+}
+
 export interface ChildInputs {
   rows?: number;
   cols?: number;
@@ -21,7 +32,10 @@ export interface ChildInputs {
   baseChildId?: string;
   resolution?: Resolution;
   imagePriorDataUrl?: string;
+  imagePriorDataUrls?: string[];
+  tileable?: boolean;
   baseImagePath?: string;
+  aspectRatio?: string;
 }
 
 export interface OpenRouterSnapshot {
@@ -34,6 +48,7 @@ export interface CompletionMetadata {
   refusal?: string;
   reasoning?: string;
   reasoningDetails?: string;
+  imageCountNote?: string;
 }
 
 export interface ChildOutputs {
@@ -41,6 +56,17 @@ export interface ChildOutputs {
   imagePaths: string[];
   primaryImagePath?: string;
   completion?: CompletionMetadata;
+  /** 0-1 per image; only present for tileable generations. */
+  seamlessScores?: number[];
+}
+
+export interface ChildSummary {
+  id: string;
+  name: string;
+  type: ChildType;
+  mode: ChildMode;
+  createdAt: string;
+  primaryImagePath?: string;
 }
 
 export interface Child {